@@ -0,0 +1,120 @@
+/*
+ * C interface of the fsst crate, with the declarations of the reference
+ * fsst.h (https://github.com/cwida/fsst) so it can replace it unchanged.
+ *
+ * Link against libfsst.a or libfsst.so built with `cargo build --release`.
+ * Tables exported here can be imported by the reference library and the
+ * other way around.
+ */
+#ifndef FSST_INCLUDED_H
+#define FSST_INCLUDED_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An upper bound on the number of bytes written by fsst_export(). */
+#define FSST_MAXHEADER (8+1+8+2048+1)
+
+/* Opaque symbol table used to compress. */
+typedef void *fsst_encoder_t;
+
+/* The symbols of a table, used to decompress. */
+typedef struct {
+   unsigned long long version;     /* version word of the serialized table */
+   unsigned char zeroTerminated;   /* code 0 is the terminator "\0" */
+   unsigned char len[255];         /* length of the symbol of each code */
+   unsigned long long symbol[255]; /* bytes of each symbol, little-endian */
+} fsst_decoder_t;
+
+/*
+ * Trains a table on n strings. With zeroTerminated set, every string is
+ * expected to end with a 0 byte, which is part of its length and encodes as
+ * the single code 0. Here and in fsst_compress(), the arrays may be NULL
+ * when n is 0.
+ */
+fsst_encoder_t *
+fsst_create(
+   size_t n,                  /* IN: number of strings */
+   size_t lenIn[],            /* IN: byte-lengths of the strings */
+   unsigned char *strIn[],    /* IN: the strings */
+   int zeroTerminated         /* IN: whether strings end with a 0 byte */
+);
+
+/* Copies a table, e.g. to compress from another thread. */
+fsst_encoder_t *
+fsst_duplicate(
+   fsst_encoder_t *encoder    /* IN: the table to copy */
+);
+
+/* Serializes a table into buf and returns the number of bytes written. */
+unsigned int
+fsst_export(
+   fsst_encoder_t *encoder,   /* IN: the table */
+   unsigned char *buf         /* OUT: at least FSST_MAXHEADER bytes */
+);
+
+/* Frees a table created by fsst_create() or fsst_duplicate(). */
+void
+fsst_destroy(
+   fsst_encoder_t *encoder
+);
+
+/* Returns the decoder of a table. */
+fsst_decoder_t
+fsst_decoder(
+   fsst_encoder_t *encoder
+);
+
+/*
+ * Reads a table serialized by fsst_export() and returns the number of bytes
+ * read, or 0 if buf does not hold a table. buf carries no length: the first
+ * 8 bytes are always read, and if they are a valid version word, the rest of
+ * the header and the symbols it announces, at most FSST_MAXHEADER bytes in
+ * all; a header announcing more than 255 symbols is rejected. buf must hold
+ * the whole serialized table. Unused codes decode to "corrupt".
+ */
+unsigned int
+fsst_import(
+   fsst_decoder_t *decoder,   /* OUT: the decoder */
+   unsigned char *buf         /* IN: the serialized table */
+);
+
+/*
+ * Compresses n strings into output, stopping at the first one that does not
+ * fit. Returns the number of strings compressed; string i is at strOut[i],
+ * lenOut[i] bytes long.
+ */
+size_t
+fsst_compress(
+   fsst_encoder_t *encoder,   /* IN: the table */
+   size_t nstrings,           /* IN: number of strings */
+   size_t lenIn[],            /* IN: byte-lengths of the strings */
+   unsigned char *strIn[],    /* IN: the strings */
+   size_t outsize,            /* IN: size of output */
+   unsigned char *output,     /* OUT: the compressed strings */
+   size_t lenOut[],           /* OUT: byte-lengths of the compressed strings */
+   unsigned char *strOut[]    /* OUT: the compressed strings */
+);
+
+/*
+ * Decompresses a string and returns its length. If that is larger than
+ * size, only the first size bytes are written. Unlike the reference, this
+ * is a function of the library rather than an inline definition.
+ */
+size_t
+fsst_decompress(
+   const fsst_decoder_t *decoder, /* IN: the decoder */
+   size_t lenIn,                  /* IN: byte-length of the compressed string */
+   const unsigned char *strIn,    /* IN: the compressed string */
+   size_t size,                   /* IN: size of output */
+   unsigned char *output          /* OUT: the decompressed string */
+);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FSST_INCLUDED_H */
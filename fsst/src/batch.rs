@@ -0,0 +1,318 @@
+use std::thread;
+
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+#[cfg(target_arch = "x86_64")]
+use crate::simd;
+
+impl Encoder {
+    /// Compresses every string of `inputs` into one contiguous buffer.
+    ///
+    /// `out` and `offsets` are cleared first. On return `offsets` holds
+    /// `inputs.len() + 1` entries and string `i` is stored in
+    /// `out[offsets[i]..offsets[i + 1]]`.
+    ///
+    /// On x86-64 CPUs with AVX2 or AVX-512, the batch is compressed with a
+    /// vectorized encoder that produces the same output.
+    pub fn encode_batch(&self, inputs: &[&[u8]], out: &mut Vec<u8>, offsets: &mut Vec<u64>) {
+        #[cfg(target_arch = "x86_64")]
+        if simd::encode_batch(self, inputs, out, offsets) {
+            return;
+        }
+
+        self.encode_batch_scalar(inputs, out, offsets);
+    }
+
+    pub(crate) fn encode_batch_scalar(
+        &self,
+        inputs: &[&[u8]],
+        out: &mut Vec<u8>,
+        offsets: &mut Vec<u64>,
+    ) {
+        out.clear();
+        offsets.clear();
+        offsets.reserve(inputs.len() + 1);
+        offsets.push(0);
+
+        for input in inputs {
+            self.encode(input, out);
+            offsets.push(out.len() as u64);
+        }
+    }
+
+    /// Like [`Encoder::encode_batch`], but splits `inputs` into
+    /// `threads` chunks of about the same size that are compressed in
+    /// parallel. The output is identical to the single-threaded one.
+    pub fn encode_batch_parallel(
+        &self,
+        inputs: &[&[u8]],
+        out: &mut Vec<u8>,
+        offsets: &mut Vec<u64>,
+        threads: usize,
+    ) {
+        let threads = threads.clamp(1, inputs.len().max(1));
+        if threads == 1 {
+            return self.encode_batch(inputs, out, offsets);
+        }
+
+        let chunks = split_balanced(inputs, threads);
+
+        let results = thread::scope(|scope| {
+            let handles = chunks
+                .iter()
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut out = Vec::new();
+                        let mut offsets = Vec::new();
+                        self.encode_batch(chunk, &mut out, &mut offsets);
+                        (out, offsets)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        out.clear();
+        out.reserve(results.iter().map(|(out, _)| out.len()).sum());
+        offsets.clear();
+        offsets.reserve(inputs.len() + 1);
+        offsets.push(0);
+
+        for (chunk_out, chunk_offsets) in results {
+            let base = out.len() as u64;
+            out.extend_from_slice(&chunk_out);
+            offsets.extend(chunk_offsets[1..].iter().map(|offset| base + offset));
+        }
+    }
+
+    /// Compresses the strings of `inputs` into the fixed-size buffer `out`
+    /// and returns how many of them fit, like the reference `fsst_compress`.
+    ///
+    /// For the `n` strings compressed, `offsets[..n + 1]` is filled as in
+    /// [`Encoder::encode_batch`]; compression stops at the first string
+    /// that does not fit entirely, so the caller can flush `out` and resume
+    /// from `inputs[n..]`.
+    ///
+    /// Panics if `offsets` is shorter than `inputs.len() + 1`.
+    pub fn encode_batch_into(
+        &self,
+        inputs: &[&[u8]],
+        out: &mut [u8],
+        offsets: &mut [u64],
+    ) -> usize {
+        assert!(offsets.len() > inputs.len());
+
+        let mut buffer = Vec::new();
+        let mut pos = 0;
+        offsets[0] = 0;
+
+        for (i, input) in inputs.iter().enumerate() {
+            buffer.clear();
+            self.encode(input, &mut buffer);
+
+            if pos + buffer.len() > out.len() {
+                return i;
+            }
+
+            out[pos..pos + buffer.len()].copy_from_slice(&buffer);
+            pos += buffer.len();
+            offsets[i + 1] = pos as u64;
+        }
+
+        inputs.len()
+    }
+}
+
+impl Decoder {
+    /// Decompresses every string of a column compressed with
+    /// [`Encoder::encode_batch`].
+    ///
+    /// `out` and `out_offsets` are cleared first and filled like the output
+    /// of `encode_batch`: string `i` ends up in
+    /// `out[out_offsets[i]..out_offsets[i + 1]]`.
+    pub fn decode_batch(
+        &self,
+        data: &[u8],
+        offsets: &[u64],
+        out: &mut Vec<u8>,
+        out_offsets: &mut Vec<u64>,
+    ) {
+        let rows = offsets.len().saturating_sub(1);
+
+        self.decode_rows(data, offsets, 0..rows, out, out_offsets);
+    }
+
+    /// Like [`Decoder::decode_batch`], but decompresses only the rows
+    /// listed in `selection`, in that order.
+    pub fn decode_batch_selected(
+        &self,
+        data: &[u8],
+        offsets: &[u64],
+        selection: &[usize],
+        out: &mut Vec<u8>,
+        out_offsets: &mut Vec<u64>,
+    ) {
+        self.decode_rows(data, offsets, selection.iter().copied(), out, out_offsets);
+    }
+
+    fn decode_rows(
+        &self,
+        data: &[u8],
+        offsets: &[u64],
+        rows: impl Iterator<Item = usize> + Clone,
+        out: &mut Vec<u8>,
+        out_offsets: &mut Vec<u64>,
+    ) {
+        // A row with decreasing offsets counts as empty here and panics when
+        // sliced below, before anything is written for it.
+        let compressed_len: usize = rows
+            .clone()
+            .map(|row| offsets[row + 1].saturating_sub(offsets[row]) as usize)
+            .sum();
+
+        out.clear();
+        out.reserve(8 * compressed_len);
+        out_offsets.clear();
+        out_offsets.push(0);
+
+        let p_base = out.as_mut_ptr();
+        let mut p_start = p_base;
+
+        for row in rows {
+            let string = &data[offsets[row] as usize..offsets[row + 1] as usize];
+
+            // Every row is counted in `compressed_len`, so there is room for it
+            unsafe {
+                p_start = self.decode_raw(string, p_start);
+                out_offsets.push(p_start.offset_from(p_base) as u64);
+            }
+        }
+
+        unsafe {
+            out.set_len(p_start.offset_from(p_base) as usize);
+        }
+    }
+}
+
+/// Splits `inputs` into at most `parts` consecutive chunks holding about the
+/// same number of bytes.
+pub(crate) fn split_balanced<'a>(inputs: &'a [&'a [u8]], parts: usize) -> Vec<&'a [&'a [u8]]> {
+    let total: usize = inputs.iter().map(|input| input.len()).sum();
+    let mut chunks = Vec::with_capacity(parts);
+    let mut start = 0;
+    let mut size = 0;
+
+    for (i, input) in inputs.iter().enumerate() {
+        size += input.len();
+
+        if chunks.len() + 1 < parts && size * parts >= total * (chunks.len() + 1) {
+            chunks.push(&inputs[start..i + 1]);
+            start = i + 1;
+        }
+    }
+    chunks.push(&inputs[start..]);
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::SymbolTable;
+
+    #[test]
+    fn encode_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/email")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+        let st = SymbolTable::build(&lines);
+
+        let mut out = Vec::new();
+        let mut offsets = Vec::new();
+        st.encode_batch(&lines, &mut out, &mut offsets);
+        assert_eq!(offsets.len(), lines.len() + 1);
+
+        let mut buffer = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            buffer.clear();
+            st.encode(line, &mut buffer);
+            assert_eq!(&out[offsets[i] as usize..offsets[i + 1] as usize], buffer);
+        }
+
+        let mut small = vec![0; out.len() / 2];
+        let mut small_offsets = vec![0; lines.len() + 1];
+        let done = st.encode_batch_into(&lines, &mut small, &mut small_offsets);
+        assert!(done > 0 && done < lines.len());
+        assert_eq!(small_offsets[..done + 1], offsets[..done + 1]);
+        assert!(offsets[done + 1] as usize > small.len());
+        assert_eq!(
+            small[..offsets[done] as usize],
+            out[..offsets[done] as usize]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_batch_parallel() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/movies")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+        let st = SymbolTable::build(&lines);
+
+        let mut out = Vec::new();
+        let mut offsets = Vec::new();
+        st.encode_batch(&lines, &mut out, &mut offsets);
+
+        let mut out_par = Vec::new();
+        let mut offsets_par = Vec::new();
+        for (threads, inputs) in [(3, &lines[..]), (8, &lines[..]), (4, &lines[..2])] {
+            st.encode_batch_parallel(inputs, &mut out_par, &mut offsets_par, threads);
+            assert_eq!(offsets_par.len(), inputs.len() + 1);
+            assert_eq!(offsets_par[..], offsets[..inputs.len() + 1]);
+            assert_eq!(out_par[..], out[..offsets[inputs.len()] as usize]);
+        }
+
+        st.encode_batch_parallel(&[], &mut out_par, &mut offsets_par, 4);
+        assert_eq!(offsets_par, [0]);
+
+        st.encode_batch_parallel(
+            &[b"", b"", b"", b"", b""],
+            &mut out_par,
+            &mut offsets_par,
+            2,
+        );
+        assert_eq!(offsets_par, [0; 6]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_batch() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/email")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+        let st = SymbolTable::build(&lines);
+
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        st.encode_batch(&lines, &mut data, &mut offsets);
+
+        let mut out = Vec::new();
+        let mut out_offsets = Vec::new();
+        st.decode_batch(&data, &offsets, &mut out, &mut out_offsets);
+        assert_eq!(out, lines.concat());
+        assert_eq!(out_offsets.len(), lines.len() + 1);
+
+        let selection = [7, 3, 1000, 3];
+        st.decode_batch_selected(&data, &offsets, &selection, &mut out, &mut out_offsets);
+        for (i, &row) in selection.iter().enumerate() {
+            let string = &out[out_offsets[i] as usize..out_offsets[i + 1] as usize];
+            assert_eq!(string, lines[row]);
+        }
+
+        Ok(())
+    }
+}
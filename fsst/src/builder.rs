@@ -0,0 +1,319 @@
+use std::thread;
+
+use crate::counters::{Counters, TABLE_LENGTH};
+use crate::lossy_pht::hash;
+use crate::table::{SymbolTable, SYMBOL_LENGTH};
+use crate::trainer::Trainer;
+
+const GENERATIONS: [usize; 5] = [8, 38, 68, 98, 128];
+const FSST_SAMPLETARGET: usize = 1 << 14;
+const FSST_SAMPLELINE: usize = 512;
+const FSST_SAMPLESEED: u64 = 4637947;
+
+/// Training parameters for a [`SymbolTable`].
+///
+/// The defaults are the ones used by [`SymbolTable::build`]:
+///
+/// ```
+/// use fsst::SymbolTableBuilder;
+///
+/// let lines: Vec<&[u8]> = vec![b"ACGTACGTTGCA", b"ACGTTGCAACGT"];
+/// let st = SymbolTableBuilder::new()
+///     .max_symbol_length(4)
+///     .seed(42)
+///     .build(&lines);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SymbolTableBuilder {
+    generations: Vec<usize>,
+    sample_target: usize,
+    sample_line: usize,
+    seed: u64,
+    max_symbol_length: usize,
+    max_symbols: usize,
+    threads: usize,
+    zero_terminated: bool,
+}
+
+impl Default for SymbolTableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTableBuilder {
+    pub fn new() -> Self {
+        Self {
+            generations: GENERATIONS.to_vec(),
+            sample_target: FSST_SAMPLETARGET,
+            sample_line: FSST_SAMPLELINE,
+            seed: FSST_SAMPLESEED,
+            max_symbol_length: SYMBOL_LENGTH,
+            max_symbols: TABLE_LENGTH - 1,
+            threads: 1,
+            zero_terminated: false,
+        }
+    }
+
+    /// Sets the sample fraction (out of 128) of each training generation.
+    /// Symbols are only extended while the fraction is below 128, so the last
+    /// generation should normally be 128.
+    pub fn generations(mut self, generations: &[usize]) -> Self {
+        self.generations = generations.to_vec();
+        self
+    }
+
+    /// Sets the number of bytes sampled from the input for training. Inputs
+    /// smaller than this are used whole.
+    pub fn sample_size(mut self, sample_size: usize) -> Self {
+        self.sample_target = sample_size;
+        self
+    }
+
+    /// Sets the length of the chunks that long strings are cut into when
+    /// sampled. Panics if `sample_line` is zero.
+    pub fn sample_line(mut self, sample_line: usize) -> Self {
+        assert!(sample_line > 0, "sample line length must not be zero");
+
+        self.sample_line = sample_line;
+        self
+    }
+
+    /// Sets the seed of the generator that picks the sampled strings.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the maximum length in bytes of a symbol, between 1 and 8.
+    pub fn max_symbol_length(mut self, max_symbol_length: usize) -> Self {
+        assert!(
+            (1..=SYMBOL_LENGTH).contains(&max_symbol_length),
+            "maximum symbol length must be between 1 and {}",
+            SYMBOL_LENGTH
+        );
+
+        self.max_symbol_length = max_symbol_length;
+        self
+    }
+
+    /// Sets the maximum number of symbols in the table, at most 255.
+    pub fn max_symbols(mut self, max_symbols: usize) -> Self {
+        assert!(
+            max_symbols < TABLE_LENGTH,
+            "a symbol table holds at most {} symbols",
+            TABLE_LENGTH - 1
+        );
+
+        self.max_symbols = max_symbols;
+        self
+    }
+
+    /// Sets how many threads count symbol frequencies on the sample during
+    /// training. The table is the same for any number of threads.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Reserves code 0 for the byte 0, like the zero-terminated mode of the
+    /// reference implementation. Every string is then compressed followed by
+    /// code 0, and since no other code or escaped byte is 0, strings without
+    /// a 0 byte compress to C strings. Decoding drops that final code 0, so
+    /// strings with embedded 0 bytes still round-trip, though their
+    /// compressed form is cut short by `strlen`. Code 0 counts towards
+    /// [`SymbolTableBuilder::max_symbols`].
+    pub fn zero_terminated(mut self, zero_terminated: bool) -> Self {
+        self.zero_terminated = zero_terminated;
+        self
+    }
+
+    /// Trains a symbol table on a sample of `text`.
+    pub fn build(&self, text: &[&[u8]]) -> SymbolTable {
+        let mut st = Trainer::new();
+        let mut counters = Counters::new();
+
+        let mut sample_memory = Vec::with_capacity(self.sample_target + self.sample_line);
+        let sample = self.make_sample(&mut sample_memory, text);
+
+        let max_symbols = self
+            .max_symbols
+            .saturating_sub(self.zero_terminated as usize);
+        let threads = self.threads.min(sample.len()).max(1);
+        let mut shard_counters = (1..threads).map(|_| Counters::new()).collect::<Vec<_>>();
+        let shard_len = sample.len().div_ceil(threads);
+
+        for &x in self.generations.iter() {
+            if threads == 1 {
+                for line in sample.iter() {
+                    /*if x < 128 && ((hash(i as u64) & 127) as usize) > x {
+                        continue;
+                    }*/
+
+                    st.compress_count(line, &mut counters);
+                }
+            } else {
+                let mut shards = sample.chunks(shard_len);
+                let first = shards.next().unwrap();
+                let st = &st;
+
+                thread::scope(|scope| {
+                    for (shard, shard_counters) in shards.zip(shard_counters.iter_mut()) {
+                        scope.spawn(move || {
+                            for line in shard {
+                                st.compress_count(line, shard_counters);
+                            }
+                        });
+                    }
+
+                    for line in first {
+                        st.compress_count(line, &mut counters);
+                    }
+                });
+
+                for shard_counters in shard_counters.iter_mut() {
+                    counters.merge(shard_counters);
+                    shard_counters.clear();
+                }
+            }
+
+            st.make_table(&counters, x, self.max_symbol_length, max_symbols);
+            counters.clear();
+        }
+
+        st.finish(self.zero_terminated)
+    }
+
+    pub(crate) fn make_sample<'a>(
+        &self,
+        sample_buf: &'a mut Vec<u8>,
+        text: &'a [&'a [u8]],
+    ) -> Vec<&'a [u8]> {
+        let total_size: usize = text.iter().map(|s| s.len()).sum();
+        if total_size < self.sample_target {
+            return text.to_owned();
+        }
+
+        let mut ranges = Vec::new();
+        let mut sample_rnd = hash(self.seed);
+
+        while sample_buf.len() < self.sample_target {
+            sample_rnd = hash(sample_rnd);
+            let line_nr = (sample_rnd as usize) % text.len();
+
+            let Some(line) = (line_nr..text.len())
+                .chain(0..line_nr)
+                .map(|line_nr| text[line_nr])
+                .find(|line| !line.is_empty())
+            else {
+                break;
+            };
+
+            let chunks = 1 + ((line.len() - 1) / self.sample_line);
+            sample_rnd = hash(sample_rnd);
+            let chunk = self.sample_line * ((sample_rnd as usize) % chunks);
+
+            let len = self.sample_line.min(line.len() - chunk);
+
+            ranges.push(sample_buf.len()..sample_buf.len() + len);
+            sample_buf.extend_from_slice(&line[chunk..chunk + len]);
+        }
+
+        let sample_buf: &'a [u8] = sample_buf;
+
+        ranges.into_iter().map(|range| &sample_buf[range]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn parameters() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/l_comment")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+
+        // Exported as: version, number of symbols, symbol lengths, ...
+        let symbol_lengths = |st: &SymbolTable| {
+            let exported = st.export();
+            exported[2..2 + exported[1] as usize].to_vec()
+        };
+
+        let default = symbol_lengths(&SymbolTable::build(&lines));
+        assert!(default.len() > 100 && default.iter().any(|&len| len > 3));
+
+        let st = SymbolTableBuilder::new()
+            .max_symbol_length(3)
+            .max_symbols(100)
+            .sample_size(1 << 12)
+            .sample_line(64)
+            .build(&lines);
+
+        let lengths = symbol_lengths(&st);
+        assert!(!lengths.is_empty() && lengths.len() <= 100);
+        assert!(lengths.iter().all(|&len| len <= 3));
+
+        // Symbols are extended once per generation below 128
+        let lengths = symbol_lengths(&SymbolTableBuilder::new().generations(&[128]).build(&lines));
+        assert!(lengths.iter().all(|&len| len == 1));
+        let lengths = symbol_lengths(
+            &SymbolTableBuilder::new()
+                .generations(&[64, 128])
+                .build(&lines),
+        );
+        assert!(lengths.iter().all(|&len| len <= 2) && lengths.contains(&2));
+
+        // Long lines are cut into chunks of at most `sample_line` bytes
+        let sample_lines = |builder: SymbolTableBuilder| {
+            let mut memory = Vec::new();
+            builder
+                .make_sample(&mut memory, &lines)
+                .iter()
+                .map(|line| line.len())
+                .collect::<Vec<_>>()
+        };
+        assert!(sample_lines(SymbolTableBuilder::new())
+            .iter()
+            .any(|&len| len > 16));
+        let chunks = sample_lines(SymbolTableBuilder::new().sample_line(16));
+        assert!(chunks.iter().all(|&len| len <= 16) && chunks.contains(&16));
+
+        // The seed picks the sampled strings and the sample size their total
+        let sample = |builder: SymbolTableBuilder| {
+            let mut memory = Vec::new();
+            builder.make_sample(&mut memory, &lines).concat()
+        };
+
+        let default = sample(SymbolTableBuilder::new());
+        assert_ne!(sample(SymbolTableBuilder::new().seed(42)), default);
+        assert!(default.len() >= FSST_SAMPLETARGET);
+        assert!(sample(SymbolTableBuilder::new().sample_size(1 << 18)).len() >= 1 << 18);
+        assert!(sample(SymbolTableBuilder::new().sample_size(1 << 10)).len() < FSST_SAMPLETARGET);
+
+        assert_eq!(
+            SymbolTableBuilder::new()
+                .sample_size(1 << 18)
+                .threads(3)
+                .build(&lines)
+                .export(),
+            SymbolTableBuilder::new()
+                .sample_size(1 << 18)
+                .build(&lines)
+                .export()
+        );
+
+        let mut buffer = Vec::new();
+        let mut decoded = Vec::new();
+        for line in lines {
+            buffer.clear();
+            st.encode(line, &mut buffer);
+            st.decode(&buffer, &mut decoded);
+            assert_eq!(decoded, line);
+        }
+
+        Ok(())
+    }
+}
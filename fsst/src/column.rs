@@ -0,0 +1,300 @@
+use std::io::{self, Read, Write};
+
+use crate::error::{invalid_data, invalid_input};
+use crate::search::{PrefixMatcher, SubstringMatcher};
+use crate::table::SymbolTable;
+use crate::varint::{read_varint, write_varint};
+
+const MAGIC: &[u8; 4] = b"FSST";
+const COLUMN_VERSION: u8 = 1;
+
+/// A column of strings compressed with a single [`SymbolTable`].
+///
+/// String `i` is stored in `data[offsets[i]..offsets[i + 1]]`.
+pub struct CompressedColumn {
+    table: SymbolTable,
+    data: Vec<u8>,
+    offsets: Vec<u64>,
+}
+
+impl CompressedColumn {
+    /// Trains a symbol table on `strings` and compresses them with it.
+    pub fn compress(strings: &[&[u8]]) -> Self {
+        Self::compress_with(SymbolTable::build(strings), strings)
+    }
+
+    /// Compresses `strings` with an already trained `table`.
+    pub fn compress_with(table: SymbolTable, strings: &[&[u8]]) -> Self {
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        table.encode_batch(strings, &mut data, &mut offsets);
+
+        Self {
+            table,
+            data,
+            offsets,
+        }
+    }
+
+    /// Like [`CompressedColumn::compress`], but compresses the strings on
+    /// `threads` threads, see [`SymbolTable::encode_batch_parallel`].
+    pub fn compress_parallel(strings: &[&[u8]], threads: usize) -> Self {
+        let table = SymbolTable::build(strings);
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        table.encode_batch_parallel(strings, &mut data, &mut offsets, threads);
+
+        Self {
+            table,
+            data,
+            offsets,
+        }
+    }
+
+    pub fn table(&self) -> &SymbolTable {
+        &self.table
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn offsets(&self) -> &[u64] {
+        &self.offsets
+    }
+
+    /// Number of strings in the column.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decompresses string `row` alone.
+    pub fn get(&self, row: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.get_into(row, &mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    /// Decompresses string `row` into `buffer`, overwriting its contents.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the column has no such
+    /// row, like [`ContainerReader::get_into`](crate::ContainerReader::get_into).
+    pub fn get_into(&self, row: usize, buffer: &mut Vec<u8>) -> io::Result<()> {
+        if row >= self.len() {
+            return Err(invalid_input(format!("row {} out of bounds", row)));
+        }
+
+        let string = &self.data[self.offsets[row] as usize..self.offsets[row + 1] as usize];
+
+        self.table.try_decode(string, buffer).map_err(invalid_data)
+    }
+
+    /// Decompresses the whole column, see [`SymbolTable::decode_batch`].
+    pub fn decompress(&self, out: &mut Vec<u8>, out_offsets: &mut Vec<u64>) {
+        self.table
+            .decode_batch(&self.data, &self.offsets, out, out_offsets);
+    }
+
+    /// Returns a bitmap of the rows equal to `needle`, bit `row % 64` of word
+    /// `row / 64`, comparing compressed bytes with
+    /// [`SymbolTable::encode_constant`].
+    pub fn filter_eq(&self, needle: &[u8]) -> Vec<u64> {
+        let needle = self.table.encode_constant(needle);
+        let mut bitmap = vec![0; self.len().div_ceil(64)];
+
+        for (row, w) in self.offsets.windows(2).enumerate() {
+            if self.data[w[0] as usize..w[1] as usize] == needle[..] {
+                bitmap[row / 64] |= 1 << (row % 64);
+            }
+        }
+
+        bitmap
+    }
+
+    /// Returns the rows starting with `prefix`, see [`PrefixMatcher`].
+    pub fn filter_prefix(&self, prefix: &[u8]) -> Vec<usize> {
+        PrefixMatcher::new(&self.table, prefix).filter(&self.data, &self.offsets)
+    }
+
+    /// Returns the rows containing `needle`, see [`SubstringMatcher`].
+    pub fn filter_contains(&self, needle: &[u8]) -> Vec<usize> {
+        SubstringMatcher::new(&self.table, needle).filter(&self.data, &self.offsets)
+    }
+
+    /// Serializes the column as: a magic number, a version byte, the exported
+    /// symbol table, the number of strings, the compressed length of each
+    /// string and the compressed bytes. Lengths are LEB128 varints.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let table = self.table.export();
+        let mut header = Vec::with_capacity(4 + 1 + 10 + table.len() + 10);
+
+        header.extend_from_slice(MAGIC);
+        header.push(COLUMN_VERSION);
+        write_varint(&mut header, table.len() as u64)?;
+        header.extend_from_slice(&table);
+        write_varint(&mut header, self.len() as u64)?;
+        writer.write_all(&header)?;
+
+        let mut lengths = Vec::with_capacity(self.len());
+        for w in self.offsets.windows(2) {
+            write_varint(&mut lengths, w[1] - w[0])?;
+        }
+        writer.write_all(&lengths)?;
+
+        writer.write_all(&self.data)
+    }
+
+    /// Reads a column written by [`CompressedColumn::write_to`].
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 5];
+        reader.read_exact(&mut magic)?;
+
+        if &magic[..4] != MAGIC {
+            return Err(invalid_data("not an FSST column"));
+        }
+
+        if magic[4] != COLUMN_VERSION {
+            return Err(invalid_data("unsupported FSST column version"));
+        }
+
+        let len = read_varint(&mut reader)?;
+        let table = read_bytes(&mut reader, len)?;
+        let table = SymbolTable::import(&table).map_err(invalid_data)?;
+
+        let len = read_len(&mut reader)?;
+        let mut offsets = Vec::with_capacity(len.min(1 << 20) + 1);
+        offsets.push(0u64);
+
+        for _ in 0..len {
+            let end = offsets[offsets.len() - 1]
+                .checked_add(read_varint(&mut reader)?)
+                .ok_or_else(|| invalid_data("FSST column too long"))?;
+            offsets.push(end);
+        }
+
+        // Lengths come from the file, so the data is read before trusting
+        // them with an allocation, and every row must decode
+        let data = read_bytes(&mut reader, offsets[len])?;
+
+        for w in offsets.windows(2) {
+            table
+                .decoded_len(&data[w[0] as usize..w[1] as usize])
+                .map_err(invalid_data)?;
+        }
+
+        Ok(Self {
+            table,
+            data,
+            offsets,
+        })
+    }
+}
+
+fn read_len<R: Read>(reader: &mut R) -> io::Result<usize> {
+    usize::try_from(read_varint(reader)?).map_err(invalid_data)
+}
+
+/// Reads exactly `len` bytes, allocating only as much as is actually read.
+fn read_bytes<R: Read>(reader: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn write_read() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/city")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+        let column = CompressedColumn::compress(&lines);
+
+        let mut serialized = Vec::new();
+        column.write_to(&mut serialized)?;
+        let column = CompressedColumn::read_from(&serialized[..])?;
+        assert_eq!(column.len(), lines.len());
+
+        let mut out = Vec::new();
+        let mut out_offsets = Vec::new();
+        column.decompress(&mut out, &mut out_offsets);
+        assert_eq!(out, lines.concat());
+
+        for row in [0, 1, 17, lines.len() - 1] {
+            assert_eq!(column.get(row)?, lines[row]);
+        }
+
+        assert_eq!(
+            column.get(lines.len()).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        assert!(CompressedColumn::read_from(&serialized[..serialized.len() - 1]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_corrupt() -> Result<(), Box<dyn std::error::Error>> {
+        // An empty column ends with its row count, 0
+        let mut header = Vec::new();
+        CompressedColumn::compress(&[]).write_to(&mut header)?;
+        header.pop();
+
+        let column = |lengths: &[u64], data: &[u8]| -> io::Result<CompressedColumn> {
+            let mut serialized = header.clone();
+            write_varint(&mut serialized, lengths.len() as u64)?;
+            for &len in lengths {
+                write_varint(&mut serialized, len)?;
+            }
+            serialized.extend_from_slice(data);
+
+            CompressedColumn::read_from(&serialized[..])
+        };
+
+        assert_eq!(column(&[2], &[255, b'c'])?.get(0)?, b"c");
+
+        // Trailing escape, lengths that overflow and data that is not there
+        assert!(column(&[3], &[255, b'c', 255]).is_err());
+        assert!(column(&[u64::MAX, 2], &[255, b'c']).is_err());
+        assert!(column(&[1 << 60], &[255, b'c']).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn filter_eq() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/email")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+        let column = CompressedColumn::compress(&lines);
+
+        for needle in [
+            lines[0],
+            lines[lines.len() / 2],
+            b"",
+            b"nobody@example.invalid",
+        ] {
+            let bitmap = column.filter_eq(needle);
+            assert_eq!(bitmap.len(), lines.len().div_ceil(64));
+
+            for (row, line) in lines.iter().enumerate() {
+                assert_eq!(bitmap[row / 64] >> (row % 64) & 1 == 1, *line == needle);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,596 @@
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+
+use crate::builder::SymbolTableBuilder;
+use crate::error::{invalid_data, invalid_input};
+use crate::table::SymbolTable;
+use crate::varint::{read_varint, write_varint};
+
+const CONTAINER_MAGIC: &[u8; 4] = b"FSSC";
+const CONTAINER_VERSION: u8 = 1;
+const HEADER: u64 = 5;
+const FOOTER: u64 = 3 * 8 + 4;
+const INDEX_ENTRY: u64 = 3 * 8;
+const BLOCK_SIZE: usize = 1 << 20;
+
+const NEW_TABLE: u8 = 0;
+const REUSED_TABLE: u8 = 1;
+
+#[derive(Clone, Copy, Debug)]
+struct BlockEntry {
+    offset: u64,
+    first_row: u64,
+    table_block: u64,
+}
+
+/// Writes records into a block container: every block of about
+/// `block_size` bytes of input is compressed with its own [`SymbolTable`],
+/// so the compression adapts to data whose content drifts.
+///
+/// The container is: a magic number and a version byte, then the blocks,
+/// the block index and a fixed-size footer. A block starts with a flag
+/// telling whether it stores a new symbol table (preceded by its length) or
+/// reuses the table of the previous block, followed by the number of
+/// records, their compressed lengths and the compressed bytes. The index
+/// holds, for every block, its offset, its first row and the block that
+/// stores its table. The footer holds the offset of the index, the number of
+/// blocks and of rows, and the magic number again. Lengths inside blocks are
+/// LEB128 varints, all other integers are little-endian `u64`.
+pub struct ContainerWriter<W: Write> {
+    inner: W,
+    builder: SymbolTableBuilder,
+    block_size: usize,
+    reuse_threshold: Option<f64>,
+    table: Option<(Box<SymbolTable>, f64)>,
+    table_block: u64,
+    data: Vec<u8>,
+    offsets: Vec<usize>,
+    index: Vec<BlockEntry>,
+    position: u64,
+    rows: u64,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            builder: SymbolTableBuilder::new(),
+            block_size: BLOCK_SIZE,
+            reuse_threshold: None,
+            table: None,
+            table_block: 0,
+            data: Vec::new(),
+            offsets: vec![0],
+            index: Vec::new(),
+            position: 0,
+            rows: 0,
+        }
+    }
+
+    /// Sets how many bytes of input are collected in a block.
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Reuses the symbol table of the previous block as long as its
+    /// compression ratio on the new block is at most `threshold` (e.g. `0.1`
+    /// for 10%) worse than on the block it was trained on.
+    pub fn with_table_reuse(mut self, threshold: f64) -> Self {
+        self.reuse_threshold = Some(threshold);
+        self
+    }
+
+    /// Sets the parameters used to train the symbol tables.
+    pub fn with_builder(mut self, builder: SymbolTableBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Appends a record, compressing the current block once it is full.
+    pub fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        self.data.extend_from_slice(record);
+        self.offsets.push(self.data.len());
+
+        if self.data.len() >= self.block_size {
+            self.write_block()?;
+        }
+
+        Ok(())
+    }
+
+    /// Compresses the last block and writes the index and the footer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.offsets.len() > 1 {
+            self.write_block()?;
+        }
+
+        if self.position == 0 {
+            self.write_header()?;
+        }
+
+        let mut index = Vec::with_capacity(self.index.len() * INDEX_ENTRY as usize);
+        for entry in &self.index {
+            index.extend_from_slice(&entry.offset.to_le_bytes());
+            index.extend_from_slice(&entry.first_row.to_le_bytes());
+            index.extend_from_slice(&entry.table_block.to_le_bytes());
+        }
+
+        index.extend_from_slice(&self.position.to_le_bytes());
+        index.extend_from_slice(&(self.index.len() as u64).to_le_bytes());
+        index.extend_from_slice(&self.rows.to_le_bytes());
+        index.extend_from_slice(CONTAINER_MAGIC);
+
+        self.inner.write_all(&index)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        self.inner.write_all(CONTAINER_MAGIC)?;
+        self.inner.write_all(&[CONTAINER_VERSION])?;
+        self.position = HEADER;
+
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.position == 0 {
+            self.write_header()?;
+        }
+
+        let records = self
+            .offsets
+            .windows(2)
+            .map(|w| &self.data[w[0]..w[1]])
+            .collect::<Vec<_>>();
+
+        let mut compressed = Vec::new();
+        let mut offsets = Vec::new();
+        let mut reuse = false;
+
+        if let (Some(threshold), Some((table, ratio))) = (self.reuse_threshold, &self.table) {
+            table.encode_batch(&records, &mut compressed, &mut offsets);
+            reuse =
+                compression_ratio(self.data.len(), compressed.len()) >= ratio * (1. - threshold);
+        }
+
+        let mut block = Vec::new();
+
+        if reuse {
+            block.push(REUSED_TABLE);
+        } else {
+            let table = self.builder.build(&records);
+            table.encode_batch(&records, &mut compressed, &mut offsets);
+
+            let exported = table.export();
+            block.push(NEW_TABLE);
+            write_varint(&mut block, exported.len() as u64)?;
+            block.extend_from_slice(&exported);
+
+            let ratio = compression_ratio(self.data.len(), compressed.len());
+            self.table = Some((Box::new(table), ratio));
+            self.table_block = self.index.len() as u64;
+        }
+
+        write_varint(&mut block, records.len() as u64)?;
+        for w in offsets.windows(2) {
+            write_varint(&mut block, w[1] - w[0])?;
+        }
+
+        self.inner.write_all(&block)?;
+        self.inner.write_all(&compressed)?;
+
+        self.index.push(BlockEntry {
+            offset: self.position,
+            first_row: self.rows,
+            table_block: self.table_block,
+        });
+
+        self.position += (block.len() + compressed.len()) as u64;
+        self.rows += records.len() as u64;
+        self.data.clear();
+        self.offsets.truncate(1);
+
+        Ok(())
+    }
+}
+
+fn compression_ratio(raw: usize, compressed: usize) -> f64 {
+    raw as f64 / compressed.max(1) as f64
+}
+
+/// Reads a container written by [`ContainerWriter`], one block at a time.
+///
+/// The block index is loaded when the container is opened, so any block can
+/// be read without scanning the ones before it. Single rows can be read with
+/// [`ContainerReader::get`]: the compressed bytes of the last block used are
+/// kept in memory and only the requested row is decompressed.
+pub struct ContainerReader<R: Read + Seek> {
+    inner: BufReader<R>,
+    index: Vec<BlockEntry>,
+    index_offset: u64,
+    rows: u64,
+    table: Option<(u64, Box<SymbolTable>)>,
+    block: Option<(usize, Vec<u8>, Vec<u64>)>,
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    pub fn open(inner: R) -> io::Result<Self> {
+        let mut inner = BufReader::new(inner);
+
+        let mut header = [0; HEADER as usize];
+        inner.read_exact(&mut header)?;
+
+        if &header[..4] != CONTAINER_MAGIC {
+            return Err(invalid_data("not an FSST container"));
+        }
+
+        if header[4] != CONTAINER_VERSION {
+            return Err(invalid_data("unsupported FSST container version"));
+        }
+
+        let end = inner.seek(SeekFrom::End(0))?;
+        if end < HEADER + FOOTER {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let mut footer = [0; FOOTER as usize];
+        inner.seek(SeekFrom::Start(end - FOOTER))?;
+        inner.read_exact(&mut footer)?;
+
+        if &footer[24..] != CONTAINER_MAGIC {
+            return Err(invalid_data("truncated FSST container"));
+        }
+
+        let index_offset = read_u64(&footer[0..]);
+        let blocks = read_u64(&footer[8..]);
+        let rows = read_u64(&footer[16..]);
+
+        if index_offset < HEADER
+            || index_offset > end - FOOTER
+            || Some(end - FOOTER - index_offset) != blocks.checked_mul(INDEX_ENTRY)
+        {
+            return Err(invalid_data("invalid FSST container index"));
+        }
+
+        let mut buffer = vec![0; (blocks * INDEX_ENTRY) as usize];
+        inner.seek(SeekFrom::Start(index_offset))?;
+        inner.read_exact(&mut buffer)?;
+
+        let index = buffer
+            .chunks_exact(INDEX_ENTRY as usize)
+            .map(|entry| BlockEntry {
+                offset: read_u64(&entry[0..]),
+                first_row: read_u64(&entry[8..]),
+                table_block: read_u64(&entry[16..]),
+            })
+            .collect::<Vec<_>>();
+
+        for (block, entry) in index.iter().enumerate() {
+            let next = index.get(block + 1);
+            let next_offset = next.map_or(index_offset, |next| next.offset);
+            let next_row = next.map_or(rows, |next| next.first_row);
+
+            if entry.offset < HEADER
+                || entry.offset >= next_offset
+                || entry.first_row >= next_row
+                || entry.table_block > block as u64
+                || (block == 0 && entry.first_row != 0)
+            {
+                return Err(invalid_data("invalid FSST container index"));
+            }
+        }
+
+        if index.is_empty() && rows != 0 {
+            return Err(invalid_data("invalid FSST container index"));
+        }
+
+        Ok(Self {
+            inner,
+            index,
+            index_offset,
+            rows,
+            table: None,
+            block: None,
+        })
+    }
+
+    /// Number of records in the container.
+    pub fn len(&self) -> u64 {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Rows stored in `block`, or `None` if the container has no such block.
+    pub fn block_rows(&self, block: usize) -> Option<Range<u64>> {
+        let first_row = self.index.get(block)?.first_row;
+        let end = self
+            .index
+            .get(block + 1)
+            .map_or(self.rows, |entry| entry.first_row);
+
+        Some(first_row..end)
+    }
+
+    /// Block that stores `row`, or `None` if the container has no such row.
+    pub fn block_of_row(&self, row: u64) -> Option<usize> {
+        if row >= self.rows {
+            return None;
+        }
+
+        Some(self.index.partition_point(|entry| entry.first_row <= row) - 1)
+    }
+
+    /// Decompresses `block` like [`SymbolTable::decode_batch`].
+    pub fn read_block(
+        &mut self,
+        block: usize,
+        out: &mut Vec<u8>,
+        out_offsets: &mut Vec<u64>,
+    ) -> io::Result<()> {
+        self.load_block(block)?;
+
+        let table = &self.table.as_ref().unwrap().1;
+        let (_, data, offsets) = self.block.as_ref().unwrap();
+
+        for row in 0..offsets.len() - 1 {
+            table
+                .decoded_len(&data[offsets[row] as usize..offsets[row + 1] as usize])
+                .map_err(invalid_data)?;
+        }
+
+        table.decode_batch(data, offsets, out, out_offsets);
+
+        Ok(())
+    }
+
+    /// Decompresses a single row.
+    pub fn get(&mut self, row: u64) -> io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.get_into(row, &mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    /// Decompresses a single row into `buffer`, overwriting its contents.
+    pub fn get_into(&mut self, row: u64, buffer: &mut Vec<u8>) -> io::Result<()> {
+        let block = self
+            .block_of_row(row)
+            .ok_or_else(|| invalid_input(format!("row {} out of bounds", row)))?;
+        self.load_block(block)?;
+
+        let row = (row - self.index[block].first_row) as usize;
+        let table = &self.table.as_ref().unwrap().1;
+        let (_, data, offsets) = self.block.as_ref().unwrap();
+
+        table
+            .try_decode(
+                &data[offsets[row] as usize..offsets[row + 1] as usize],
+                buffer,
+            )
+            .map_err(invalid_data)
+    }
+
+    /// Reads `block` into the cache, unless it is already there.
+    fn load_block(&mut self, block: usize) -> io::Result<()> {
+        if block >= self.index.len() {
+            return Err(invalid_input(format!("block {} out of bounds", block)));
+        }
+
+        if self.block.as_ref().map(|b| b.0) != Some(block) {
+            self.block = None;
+
+            let (data, offsets) = self.read_compressed_block(block)?;
+            self.block = Some((block, data, offsets));
+        }
+
+        Ok(())
+    }
+
+    /// Reads the compressed bytes and offsets of `block` and loads its table.
+    fn read_compressed_block(&mut self, block: usize) -> io::Result<(Vec<u8>, Vec<u64>)> {
+        let entry = self.index[block];
+
+        self.inner.seek(SeekFrom::Start(entry.offset))?;
+
+        let mut flag = [0];
+        self.inner.read_exact(&mut flag)?;
+
+        match flag[0] {
+            NEW_TABLE if entry.table_block == block as u64 => self.read_table(block as u64)?,
+            REUSED_TABLE if entry.table_block < block as u64 => {
+                if self.table.as_ref().map(|t| t.0) != Some(entry.table_block) {
+                    let position = self.inner.stream_position()?;
+                    self.inner.seek(SeekFrom::Start(
+                        self.index[entry.table_block as usize].offset + 1,
+                    ))?;
+                    self.read_table(entry.table_block)?;
+                    self.inner.seek(SeekFrom::Start(position))?;
+                }
+            }
+            _ => return Err(invalid_data("invalid FSST container block")),
+        }
+
+        let rows = self.block_rows(block).unwrap();
+        if read_varint(&mut self.inner)? != rows.end - rows.start {
+            return Err(invalid_data("invalid FSST container block"));
+        }
+
+        let mut offsets = Vec::with_capacity((rows.end - rows.start).min(1 << 20) as usize + 1);
+        offsets.push(0u64);
+
+        for _ in rows {
+            let end = offsets[offsets.len() - 1].saturating_add(read_varint(&mut self.inner)?);
+            offsets.push(end);
+        }
+
+        let block_end = self
+            .index
+            .get(block + 1)
+            .map_or(self.index_offset, |next| next.offset);
+        let len = offsets[offsets.len() - 1];
+
+        if len > block_end.saturating_sub(self.inner.stream_position()?) {
+            return Err(invalid_data("invalid FSST container block"));
+        }
+
+        let mut data = vec![0; len as usize];
+        self.inner.read_exact(&mut data)?;
+
+        Ok((data, offsets))
+    }
+
+    /// Reads the table at the current position unless `block`'s is loaded.
+    fn read_table(&mut self, block: u64) -> io::Result<()> {
+        let len = read_varint(&mut self.inner)?;
+
+        if self.table.as_ref().map(|t| t.0) == Some(block) {
+            self.inner.seek_relative(len as i64)?;
+            return Ok(());
+        }
+
+        let mut table = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut table)?;
+        let table = SymbolTable::import(&table).map_err(invalid_data)?;
+        self.table = Some((block, Box::new(table)));
+
+        Ok(())
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::column::CompressedColumn;
+
+    #[test]
+    fn blocks() -> Result<(), Box<dyn std::error::Error>> {
+        let location = fs::read("tests.nosync/cwida/location")?;
+        let wiki = fs::read("tests.nosync/cwida/wiki")?;
+        let lines = location
+            .split(|&c| c == b'\n')
+            .chain(wiki.split(|&c| c == b'\n'))
+            .collect::<Vec<_>>();
+
+        let mut writer = ContainerWriter::new(Vec::new()).with_block_size(1 << 18);
+        for line in &lines {
+            writer.write_record(line)?;
+        }
+        let container = writer.finish()?;
+
+        let mut single = Vec::new();
+        CompressedColumn::compress(&lines).write_to(&mut single)?;
+        assert!(container.len() < single.len());
+
+        let mut reader = ContainerReader::open(Cursor::new(&container))?;
+        assert_eq!(reader.len(), lines.len() as u64);
+        assert!(reader.block_count() > 1);
+
+        let mut out = Vec::new();
+        let mut out_offsets = Vec::new();
+        for block in (0..reader.block_count()).rev() {
+            let rows = reader.block_rows(block).unwrap();
+            assert_eq!(reader.block_of_row(rows.start), Some(block));
+
+            reader.read_block(block, &mut out, &mut out_offsets)?;
+            let expected = &lines[rows.start as usize..rows.end as usize];
+            assert_eq!(out, expected.concat());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn table_reuse() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read("tests.nosync/cwida/l_comment")?;
+        let lines = file.split(|&c| c == b'\n').collect::<Vec<_>>();
+
+        let mut writer = ContainerWriter::new(Vec::new())
+            .with_block_size(1 << 16)
+            .with_table_reuse(0.2);
+        for line in &lines {
+            writer.write_record(line)?;
+        }
+        let container = writer.finish()?;
+
+        let mut reader = ContainerReader::open(Cursor::new(&container))?;
+        let tables = reader
+            .index
+            .iter()
+            .enumerate()
+            .filter(|(block, entry)| entry.table_block == *block as u64)
+            .count();
+        assert!(tables < reader.block_count());
+
+        let mut out = Vec::new();
+        let mut out_offsets = Vec::new();
+        for block in [3, 0, reader.block_count() - 1] {
+            reader.read_block(block, &mut out, &mut out_offsets)?;
+            let rows = reader.block_rows(block).unwrap();
+            assert_eq!(out, lines[rows.start as usize..rows.end as usize].concat());
+        }
+
+        let empty = ContainerWriter::new(Vec::new()).finish()?;
+        assert!(ContainerReader::open(Cursor::new(&empty))?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn random_access() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read("tests.nosync/cwida/urls2")?;
+        let lines = file.split(|&c| c == b'\n').collect::<Vec<_>>();
+
+        let mut writer = ContainerWriter::new(Vec::new())
+            .with_block_size(1 << 16)
+            .with_table_reuse(0.1);
+        for line in &lines {
+            writer.write_record(line)?;
+        }
+        let container = writer.finish()?;
+
+        let mut reader = ContainerReader::open(Cursor::new(&container))?;
+        let mut buffer = Vec::new();
+        let mut row = 0;
+        for _ in 0..2000 {
+            row = (row * 7919 + 104_729) % lines.len();
+            reader.get_into(row as u64, &mut buffer)?;
+            assert_eq!(buffer, lines[row]);
+        }
+
+        assert_eq!(reader.get(0)?, lines[0]);
+
+        let rows = reader.len();
+        assert_eq!(reader.block_of_row(rows), None);
+        assert_eq!(reader.block_rows(reader.block_count()), None);
+        assert_eq!(
+            reader.get(rows).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            reader
+                .read_block(reader.block_count(), &mut buffer, &mut Vec::new())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        Ok(())
+    }
+}
@@ -49,6 +49,39 @@ impl Counters {
         }
     }
 
+    /// Adds the counts of `other` to these counters.
+    pub fn merge(&mut self, other: &Counters) {
+        for (bucket, &bits) in other.bitmap1.iter().enumerate() {
+            for idx in set_bits(bucket, bits) {
+                self.add_c1(idx, other.counter1[idx]);
+            }
+        }
+
+        for (bucket, &bits) in other.bitmap2.iter().enumerate() {
+            for idx in set_bits(bucket, bits) {
+                self.add_c2(idx, other.counter2[idx]);
+            }
+        }
+    }
+
+    fn add_c1(&mut self, idx: usize, count: usize) {
+        if self.is_set_c1(idx) {
+            self.counter1[idx] += count;
+        } else {
+            self.counter1[idx] = count;
+            self.set_c1(idx);
+        }
+    }
+
+    fn add_c2(&mut self, idx: usize, count: usize) {
+        if self.is_set_c2(idx) {
+            self.counter2[idx] += count;
+        } else {
+            self.counter2[idx] = count;
+            self.set_c2(idx);
+        }
+    }
+
     #[inline]
     fn is_set_c1(&self, idx: usize) -> bool {
         assert!(idx < COUNTER1_LENGTH);
@@ -119,3 +152,17 @@ impl Counters {
         }
     }
 }
+
+/// Indices of the bits set in word `bucket` of a bitmap.
+fn set_bits(bucket: usize, mut bits: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+
+        let idx = bucket * 64 + bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+
+        Some(idx)
+    })
+}
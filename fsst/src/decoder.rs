@@ -0,0 +1,341 @@
+use crate::error::DecodeError;
+use crate::symbol::Symbol;
+
+/// The decompression half of a [`SymbolTable`](crate::SymbolTable): the
+/// bytes and length of the symbol of every code, in flat arrays.
+///
+/// Get one with [`SymbolTable::decoder`](crate::SymbolTable::decoder).
+#[derive(Clone)]
+pub struct Decoder {
+    /// Symbol bytes, packed little-endian.
+    pub(crate) values: [u64; 256],
+    /// Symbol lengths in bytes; 0 for codes without a symbol.
+    pub(crate) lengths: [u8; 256],
+    /// Whether strings end with a code 0 terminator.
+    zero_terminated: bool,
+}
+
+impl Decoder {
+    /// Builds the decoder for `symbols`, the symbol of each code.
+    pub(crate) fn new(symbols: &[Symbol], zero_terminated: bool) -> Self {
+        let mut values = [0; 256];
+        let mut lengths = [0; 256];
+
+        for (code, s) in symbols.iter().enumerate() {
+            values[code] = s.value;
+            lengths[code] = (s.len / 8) as u8;
+        }
+
+        Self {
+            values,
+            lengths,
+            zero_terminated,
+        }
+    }
+
+    /// Returns the codes of `string` without the terminator that
+    /// [`Encoder::encode`](crate::Encoder::encode) appends in zero-terminated
+    /// mode.
+    #[inline]
+    pub(crate) fn codes<'a>(&self, string: &'a [u8]) -> &'a [u8] {
+        strip_terminator(string, self.zero_terminated)
+    }
+
+    /// Returns the symbol of each code, in code order.
+    pub(crate) fn symbols(&self) -> Vec<Symbol> {
+        self.values
+            .iter()
+            .zip(self.lengths)
+            .take_while(|&(_, len)| len > 0)
+            .map(|(&value, len)| Symbol::with(value, 8 * len as usize))
+            .collect()
+    }
+
+    /// Decompresses `string` into `buffer`, overwriting its contents.
+    ///
+    /// Panics if `string` ends with an escape code. Use
+    /// [`Decoder::try_decode`] for untrusted input.
+    pub fn decode(&self, string: &[u8], buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.reserve(8 * string.len());
+
+        unsafe { self.decode_unchecked(string, buffer) }
+    }
+
+    /// Decompresses `string` into `buffer`, overwriting its contents, and
+    /// checks that `string` is a valid code sequence for this table.
+    ///
+    /// On error `buffer` is left empty.
+    pub fn try_decode(&self, string: &[u8], buffer: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let string = self.codes(string);
+        let needed = string
+            .len()
+            .checked_mul(8)
+            .ok_or(DecodeError::OutputOverflow { needed: usize::MAX })?;
+
+        buffer.clear();
+        buffer.reserve(needed);
+
+        let mut p_start: *mut u8 = buffer.as_mut_ptr();
+        let mut i = 0;
+
+        while i < string.len() {
+            let code = string[i];
+
+            if code != 255 {
+                if self.lengths[code as usize] == 0 {
+                    return Err(DecodeError::UnknownCode { code, position: i });
+                }
+
+                unsafe {
+                    p_start = self.write_symbol(code, p_start);
+                }
+
+                i += 1;
+            } else {
+                if i + 1 >= string.len() {
+                    return Err(DecodeError::TruncatedEscape { position: i });
+                }
+
+                unsafe {
+                    *p_start = string[i + 1];
+                    p_start = p_start.add(1);
+                }
+
+                i += 2;
+            }
+        }
+
+        unsafe {
+            buffer.set_len(p_start.offset_from(buffer.as_ptr()) as usize);
+        }
+
+        Ok(())
+    }
+
+    /// Decompresses `string` into the caller-provided `out` and returns the
+    /// number of bytes written.
+    ///
+    /// Whole 8-byte words are stored while there is room for them; near the
+    /// end of `out` only the bytes of each symbol are copied, so the output
+    /// needs no slack. If `out` is too small,
+    /// [`DecodeError::OutputOverflow`] reports the size needed.
+    pub fn decode_into(&self, string: &[u8], out: &mut [u8]) -> Result<usize, DecodeError> {
+        let string = self.codes(string);
+        let mut pos = 0;
+        let mut i = 0;
+
+        while i < string.len() {
+            let code = string[i];
+
+            if code != 255 {
+                let len = self.lengths[code as usize] as usize;
+
+                if len == 0 {
+                    return Err(DecodeError::UnknownCode { code, position: i });
+                }
+
+                if pos + 8 <= out.len() {
+                    unsafe {
+                        self.write_symbol(code, out.as_mut_ptr().add(pos));
+                    }
+                } else if pos + len <= out.len() {
+                    out[pos..pos + len]
+                        .copy_from_slice(&self.values[code as usize].to_le_bytes()[..len]);
+                } else {
+                    let needed = pos + self.codes_len(&string[i..])?;
+                    return Err(DecodeError::OutputOverflow { needed });
+                }
+
+                pos += len;
+                i += 1;
+            } else {
+                if i + 1 >= string.len() {
+                    return Err(DecodeError::TruncatedEscape { position: i });
+                }
+
+                if pos >= out.len() {
+                    let needed = pos + self.codes_len(&string[i..])?;
+                    return Err(DecodeError::OutputOverflow { needed });
+                }
+
+                out[pos] = string[i + 1];
+                pos += 1;
+                i += 2;
+            }
+        }
+
+        Ok(pos)
+    }
+
+    /// Returns the decompressed length of `string` without decoding it.
+    pub fn decoded_len(&self, string: &[u8]) -> Result<usize, DecodeError> {
+        self.codes_len(self.codes(string))
+    }
+
+    /// Returns the decompressed length of `string` once the terminator has
+    /// been stripped by [`Decoder::codes`].
+    fn codes_len(&self, string: &[u8]) -> Result<usize, DecodeError> {
+        let mut len = 0;
+        let mut i = 0;
+
+        while i < string.len() {
+            let code = string[i];
+
+            if code != 255 {
+                if self.lengths[code as usize] == 0 {
+                    return Err(DecodeError::UnknownCode { code, position: i });
+                }
+
+                len += self.lengths[code as usize] as usize;
+                i += 1;
+            } else {
+                if i + 1 >= string.len() {
+                    return Err(DecodeError::TruncatedEscape { position: i });
+                }
+
+                len += 1;
+                i += 2;
+            }
+        }
+
+        Ok(len)
+    }
+
+    /// Decompresses `string` into `buffer`, overwriting its contents, without
+    /// reserving space first.
+    ///
+    /// # Safety
+    ///
+    /// The decoder writes a whole 8-byte word for every code, so `buffer`
+    /// must have a capacity of at least `8 * string.len()` bytes.
+    pub unsafe fn decode_unchecked(&self, string: &[u8], buffer: &mut Vec<u8>) {
+        unsafe {
+            let p_end = self.decode_raw(string, buffer.as_mut_ptr());
+            buffer.set_len(p_end.offset_from(buffer.as_ptr()) as usize);
+        }
+    }
+
+    /// Decompresses `string` to `p_start` and returns the end of the output.
+    ///
+    /// Like the reference `fsst_decompress`, blocks of 4 codes without an
+    /// escape are decoded without branching on each code.
+    ///
+    /// # Safety
+    ///
+    /// `p_start` must be valid for writes of `8 * string.len()` bytes.
+    #[inline]
+    pub(crate) unsafe fn decode_raw(&self, string: &[u8], mut p_start: *mut u8) -> *mut u8 {
+        let string = self.codes(string);
+        let mut i = 0;
+
+        while i + 4 <= string.len() {
+            let block = u32::from_le_bytes(string[i..i + 4].try_into().unwrap());
+            let escapes = escape_mask(block);
+
+            unsafe {
+                if escapes == 0 {
+                    p_start = self.write_symbol(block as u8, p_start);
+                    p_start = self.write_symbol((block >> 8) as u8, p_start);
+                    p_start = self.write_symbol((block >> 16) as u8, p_start);
+                    p_start = self.write_symbol((block >> 24) as u8, p_start);
+                    i += 4;
+                } else if block as u8 != 255 {
+                    p_start = self.write_symbol(block as u8, p_start);
+                    i += 1;
+                } else {
+                    *p_start = (block >> 8) as u8;
+                    p_start = p_start.add(1);
+                    i += 2;
+                }
+            }
+        }
+
+        while i < string.len() {
+            unsafe {
+                if string[i] != 255 {
+                    p_start = self.write_symbol(string[i], p_start);
+                    i += 1;
+                } else {
+                    *p_start = string[i + 1];
+                    p_start = p_start.add(1);
+                    i += 2;
+                }
+            }
+        }
+
+        p_start
+    }
+
+    /// Stores the 8 bytes of the symbol of `code` at `p` and returns the end
+    /// of the symbol.
+    ///
+    /// # Safety
+    ///
+    /// `p` must be valid for writes of 8 bytes.
+    #[inline(always)]
+    unsafe fn write_symbol(&self, code: u8, p: *mut u8) -> *mut u8 {
+        unsafe {
+            (p as *mut u64).write_unaligned(self.values[code as usize]);
+            p.add(self.lengths[code as usize] as usize)
+        }
+    }
+}
+
+/// Returns `string` without its last code if it is the terminator of a
+/// zero-terminated table. Code 0 elsewhere is the byte 0 of the input.
+#[inline]
+pub(crate) fn strip_terminator(string: &[u8], zero_terminated: bool) -> &[u8] {
+    match string.split_last() {
+        Some((0, codes)) if zero_terminated => codes,
+        _ => string,
+    }
+}
+
+/// Returns a non-zero value if one of the 4 codes packed in `block` is an
+/// escape code.
+#[inline(always)]
+fn escape_mask(block: u32) -> u32 {
+    let inverted = !block;
+
+    inverted.wrapping_sub(0x0101_0101) & block & 0x8080_8080
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::SymbolTable;
+
+    #[test]
+    fn decode() -> Result<(), Box<dyn std::error::Error>> {
+        let st = SymbolTable::import_cwida(&fs::read("tests.nosync/tables/urls.fsst")?)?;
+        let decoder = st.decoder().clone();
+        let mut buffer = Vec::new();
+
+        // Blocks with an escape at each position, one without and a tail
+        let compressed = [
+            255, b'a', 0, 255, b'b', 0, 1, 255, b'c', 0, 1, 2, 255, b'd', 0, 1, 2, 3, 4,
+        ];
+        decoder.decode(&compressed, &mut buffer);
+        assert_eq!(buffer, b"ahtbhttpchttp://dhttp://www..com/");
+
+        assert_eq!(decoder.decoded_len(&compressed)?, buffer.len());
+        assert!(decoder.try_decode(&[3, 4, 5, 6, 0], &mut buffer).is_err());
+
+        let file = fs::read("tests.nosync/cwida/hex")?;
+        let lines = file.split(|&b| b == b'\n').collect::<Vec<_>>();
+        let st = SymbolTable::build(&lines[..lines.len() / 4]);
+        let decoder = st.decoder();
+
+        let mut compressed = Vec::new();
+        for line in lines {
+            compressed.clear();
+            st.encode(line, &mut compressed);
+            decoder.decode(&compressed, &mut buffer);
+            assert_eq!(buffer, line);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::lossy_pht::{hash, HASH_TABLE_SIZE};
+use crate::symbol::Symbol;
+
+/// `meta` of an escaped byte: code 255, 1 byte.
+const ESCAPE: u16 = 255 | 1 << 8;
+
+/// The compression half of a [`SymbolTable`](crate::SymbolTable): lookup
+/// tables from the next bytes of a string to the code of the longest symbol
+/// they start with.
+///
+/// Every lookup yields a `meta` entry holding the code in bits 0-7 (255 for
+/// an escape) and the number of bytes it covers in bits 8-15. Get one with
+/// [`SymbolTable::encoder`](crate::SymbolTable::encoder).
+#[derive(Clone)]
+pub struct Encoder {
+    /// Symbols of 3 to 8 bytes, by hash of their first 3 bytes.
+    pub(crate) hash_values: Box<[u64]>,
+    /// `meta` of each slot of `hash_values`, 0 for an empty slot.
+    pub(crate) hash_meta: Box<[u16]>,
+    /// `meta` of the 2-byte symbol, 1-byte symbol or escape for each 2-byte
+    /// prefix.
+    pub(crate) short_meta: Box<[u16]>,
+    /// `meta` of the 1-byte symbol or escape for each byte.
+    byte_meta: [u16; 256],
+    /// Whether code 0 terminates every string.
+    pub(crate) zero_terminated: bool,
+}
+
+impl Encoder {
+    /// Builds the encoder for `symbols`, the symbol of each code.
+    /// `zero_terminated` tables have the byte 0 as code 0.
+    ///
+    /// A symbol of 3 bytes or more whose hash slot is already taken is never
+    /// emitted, and a later 1- or 2-byte symbol replaces an earlier equal one.
+    pub(crate) fn new(symbols: &[Symbol], zero_terminated: bool) -> Self {
+        // `hash_meta` and `short_meta` get one padding entry so they can be
+        // read 4 bytes at a time by the vectorized encoder
+        let mut hash_values = vec![0; HASH_TABLE_SIZE].into_boxed_slice();
+        let mut hash_meta = vec![0; HASH_TABLE_SIZE + 1].into_boxed_slice();
+        let mut byte_meta = [ESCAPE; 256];
+
+        for (code, s) in symbols.iter().enumerate() {
+            let meta = code as u16 | ((s.len / 8) as u16) << 8;
+
+            if s.len == 8 {
+                byte_meta[s.first1byte() as usize] = meta;
+            } else if s.len > 16 {
+                let slot = hash(s.first3byte()) as usize & (HASH_TABLE_SIZE - 1);
+
+                if hash_meta[slot] == 0 {
+                    hash_values[slot] = s.value;
+                    hash_meta[slot] = meta;
+                }
+            }
+        }
+
+        let mut short_meta = (0..=u16::MAX as usize)
+            .map(|prefix| byte_meta[prefix & 0xFF])
+            .chain([0])
+            .collect::<Box<[u16]>>();
+
+        for (code, s) in symbols.iter().enumerate() {
+            if s.len == 16 {
+                short_meta[s.first2byte() as usize] = code as u16 | 2 << 8;
+            }
+        }
+
+        Self {
+            hash_values,
+            hash_meta,
+            short_meta,
+            byte_meta,
+            zero_terminated,
+        }
+    }
+
+    /// Compresses `string`, appending the codes to `buffer`, followed by the
+    /// terminator in zero-terminated mode.
+    pub fn encode(&self, string: &[u8], buffer: &mut Vec<u8>) {
+        let mut pos = 0;
+
+        while pos + 8 <= string.len() {
+            let word = u64::from_le_bytes(string[pos..pos + 8].try_into().unwrap());
+            pos += self.encode_symbol(word, 8, buffer);
+        }
+
+        while pos < string.len() {
+            let len = string.len() - pos;
+            let mut tail = [0; 8];
+
+            tail[..len].copy_from_slice(&string[pos..]);
+            pos += self.encode_symbol(u64::from_le_bytes(tail), len, buffer);
+        }
+
+        if self.zero_terminated {
+            buffer.push(0);
+        }
+    }
+
+    /// Appends the code of the longest symbol at the start of the `len`
+    /// bytes of `word` to `buffer` and returns the length of that symbol.
+    #[inline]
+    fn encode_symbol(&self, word: u64, len: usize, buffer: &mut Vec<u8>) -> usize {
+        let meta = self.find_longest_symbol(word, len);
+        let code = meta as u8;
+
+        buffer.push(code);
+        if code == 255 {
+            buffer.push(word as u8);
+        }
+
+        (meta >> 8) as usize
+    }
+
+    /// Returns the `meta` of the longest symbol at the start of the `len`
+    /// bytes of `word`, with bytes past `len` set to zero.
+    #[inline]
+    fn find_longest_symbol(&self, word: u64, len: usize) -> u16 {
+        let slot = hash(word & 0xFF_FF_FF) as usize & (HASH_TABLE_SIZE - 1);
+        let meta = self.hash_meta[slot];
+        let symbol_len = (meta >> 8) as usize;
+
+        if meta != 0
+            && symbol_len <= len
+            && (word ^ self.hash_values[slot]) & (u64::MAX >> (64 - 8 * symbol_len)) == 0
+        {
+            meta
+        } else if len >= 2 {
+            self.short_meta[word as usize & 0xFF_FF]
+        } else {
+            self.byte_meta[word as usize & 0xFF]
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::{fmt, io};
+
+/// Error returned by [`SymbolTable::import`](crate::SymbolTable::import) when
+/// the serialized table is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    /// The buffer ended before the whole table was read.
+    UnexpectedEnd,
+    /// The table was written by an unsupported format version.
+    UnsupportedVersion(u32),
+    /// The stored checksum does not match the table contents.
+    ChecksumMismatch,
+    /// A symbol has an invalid length or the table has too many symbols.
+    InvalidSymbol(usize),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of serialized symbol table"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported symbol table version {}", v),
+            Self::ChecksumMismatch => write!(f, "symbol table checksum mismatch"),
+            Self::InvalidSymbol(code) => write!(f, "invalid symbol with code {}", code),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+/// Error returned by [`SymbolTable::try_decode`](crate::SymbolTable::try_decode)
+/// when the compressed input is not a valid code sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ends with an escape code that is not followed by a byte.
+    TruncatedEscape { position: usize },
+    /// The input contains a code that is not assigned in the symbol table.
+    UnknownCode { code: u8, position: usize },
+    /// The decompressed output does not fit in the output buffer.
+    OutputOverflow { needed: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TruncatedEscape { position } => {
+                write!(f, "truncated escape at position {}", position)
+            }
+            Self::UnknownCode { code, position } => {
+                write!(f, "unknown code {} at position {}", code, position)
+            }
+            Self::OutputOverflow { needed } => {
+                write!(f, "output buffer too small, {} bytes needed", needed)
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+pub(crate) fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+pub(crate) fn invalid_input<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidInput, error)
+}
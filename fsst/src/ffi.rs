@@ -0,0 +1,482 @@
+//! C interface with the shape of the reference `fsst.h`, declared in
+//! `include/fsst.h`.
+//!
+//! `fsst_encoder_t` is an opaque [`SymbolTable`]; `fsst_decoder_t` is the
+//! plain struct of the reference, so code written against the reference
+//! header works unchanged. As in the reference, the strings of a
+//! zero-terminated table include their terminating 0, and
+//! [`fsst_decompress`] decodes it along with every other code.
+
+use std::os::raw::{c_int, c_uint};
+use std::{ptr, slice};
+
+use crate::table::{SymbolTable, CWIDA_HEADER, CWIDA_VERSION};
+
+/// `FSST_MAXHEADER`: an upper bound on the size written by [`fsst_export`],
+/// with the value of the reference.
+pub const FSST_MAXHEADER: usize = 8 + 1 + 8 + 2048 + 1;
+
+/// `FSST_CORRUPT`: the text "corrupt", which [`fsst_import`] gives to unused
+/// codes like the reference, so corrupted code sequences show in the output.
+const FSST_CORRUPT: u64 = u64::from_le_bytes(*b"corrupt\0");
+
+/// `fsst_decoder_t`: the symbols of a table, in the layout of the reference.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FsstDecoder {
+    pub version: u64,
+    pub zero_terminated: u8,
+    pub len: [u8; 255],
+    pub symbol: [u64; 255],
+}
+
+/// Trains a table on `n` strings.
+///
+/// # Safety
+///
+/// `len_in` and `str_in` must point to `n` lengths and `n` pointers to
+/// strings of those lengths. They may be null if `n` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_create(
+    n: usize,
+    len_in: *const usize,
+    str_in: *const *const u8,
+    zero_terminated: c_int,
+) -> *mut SymbolTable {
+    let zero_terminated = zero_terminated != 0;
+    let strings = unsafe { strings(n, len_in, str_in) }
+        .map(|string| match string.split_last() {
+            Some((0, rest)) if zero_terminated => rest,
+            _ => string,
+        })
+        .collect::<Vec<_>>();
+
+    let st = crate::SymbolTableBuilder::new()
+        .zero_terminated(zero_terminated)
+        .build(&strings);
+
+    Box::into_raw(Box::new(st))
+}
+
+/// Returns a copy of `encoder`, to be used by another thread.
+///
+/// # Safety
+///
+/// `encoder` must come from [`fsst_create`] or [`fsst_duplicate`] and not be
+/// destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_duplicate(encoder: *const SymbolTable) -> *mut SymbolTable {
+    Box::into_raw(Box::new(unsafe { (*encoder).clone() }))
+}
+
+/// Frees `encoder`.
+///
+/// # Safety
+///
+/// `encoder` must come from [`fsst_create`] or [`fsst_duplicate`] and not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_destroy(encoder: *mut SymbolTable) {
+    drop(unsafe { Box::from_raw(encoder) });
+}
+
+/// Serializes `encoder` like [`SymbolTable::export_cwida`] and returns the
+/// number of bytes written, at most [`FSST_MAXHEADER`].
+///
+/// # Safety
+///
+/// `encoder` must be a live encoder and `buf` valid for writes of
+/// [`FSST_MAXHEADER`] bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_export(encoder: *const SymbolTable, buf: *mut u8) -> c_uint {
+    let exported = unsafe { (*encoder).export_cwida() };
+
+    unsafe {
+        ptr::copy_nonoverlapping(exported.as_ptr(), buf, exported.len());
+    }
+
+    exported.len() as c_uint
+}
+
+/// Reads a table serialized by [`fsst_export`] into `decoder` and returns the
+/// number of bytes read, or 0 if `buf` does not hold a valid table.
+///
+/// Unused codes decode to "corrupt", as in the reference.
+///
+/// # Safety
+///
+/// `decoder` must be valid for writes and `buf` must hold a whole serialized
+/// table, or at least its 8-byte version word: the size of the rest is read
+/// from the table's own header, and tables announcing more than 255 symbols
+/// are rejected before it is read, so at most [`FSST_MAXHEADER`] bytes are.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_import(decoder: *mut FsstDecoder, buf: *const u8) -> c_uint {
+    // Only read past the version word once it is known to be a table; the
+    // histogram then gives the size of the rest
+    let version = u64::from_le_bytes(unsafe { slice::from_raw_parts(buf, 8) }.try_into().unwrap());
+    if version >> 32 != CWIDA_VERSION {
+        return 0;
+    }
+
+    let header = unsafe { slice::from_raw_parts(buf, CWIDA_HEADER) };
+    let symbols: usize = header[9..].iter().map(|&count| count as usize).sum();
+    if symbols > 255 {
+        return 0;
+    }
+
+    let stored: usize = (1..=8).map(|len| len * header[8 + len] as usize).sum();
+    let len = CWIDA_HEADER + stored.saturating_sub(header[8] as usize & 1);
+    let buffer = unsafe { slice::from_raw_parts(buf, len) };
+
+    let Ok(st) = SymbolTable::import_cwida(buffer) else {
+        return 0;
+    };
+
+    let mut imported = FsstDecoder {
+        version,
+        zero_terminated: st.is_zero_terminated() as u8,
+        len: [8; 255],
+        symbol: [FSST_CORRUPT; 255],
+    };
+
+    for (code, s) in st.decoder().symbols().iter().enumerate() {
+        imported.len[code] = (s.len / 8) as u8;
+        imported.symbol[code] = s.value;
+    }
+
+    unsafe {
+        decoder.write(imported);
+    }
+
+    len as c_uint
+}
+
+/// Returns the decoder of `encoder`.
+///
+/// # Safety
+///
+/// `encoder` must be a live encoder.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_decoder(encoder: *const SymbolTable) -> FsstDecoder {
+    let mut buf = [0; FSST_MAXHEADER];
+    let mut decoder = FsstDecoder {
+        version: 0,
+        zero_terminated: 0,
+        len: [0; 255],
+        symbol: [0; 255],
+    };
+
+    unsafe {
+        fsst_export(encoder, buf.as_mut_ptr());
+        fsst_import(&mut decoder, buf.as_ptr());
+    }
+
+    decoder
+}
+
+/// Compresses `nstrings` strings into `output`, stopping at the first one
+/// that does not fit in the `outsize` bytes. Returns the number of strings
+/// compressed; string `i` is written at `str_out[i]`, `len_out[i]` bytes long.
+///
+/// # Safety
+///
+/// `encoder` must be a live encoder, `len_in` and `str_in` must describe
+/// `nstrings` strings, `output` must be valid for writes of `outsize` bytes
+/// and `len_out` and `str_out` valid for writes of `nstrings` entries. The
+/// arrays may be null if `nstrings` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_compress(
+    encoder: *const SymbolTable,
+    nstrings: usize,
+    len_in: *const usize,
+    str_in: *const *const u8,
+    outsize: usize,
+    output: *mut u8,
+    len_out: *mut usize,
+    str_out: *mut *mut u8,
+) -> usize {
+    let st = unsafe { &*encoder };
+    let mut buffer = Vec::new();
+    let mut pos = 0;
+
+    for (i, string) in unsafe { strings(nstrings, len_in, str_in) }.enumerate() {
+        buffer.clear();
+
+        // The terminator is part of the input; `encode` adds it back
+        match string.split_last() {
+            Some((0, rest)) if st.is_zero_terminated() => st.encode(rest, &mut buffer),
+            _ if st.is_zero_terminated() => {
+                st.encode(string, &mut buffer);
+                buffer.pop();
+            }
+            _ => st.encode(string, &mut buffer),
+        }
+
+        if buffer.len() > outsize - pos {
+            return i;
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(buffer.as_ptr(), output.add(pos), buffer.len());
+            len_out.add(i).write(buffer.len());
+            str_out.add(i).write(output.add(pos));
+        }
+
+        pos += buffer.len();
+    }
+
+    nstrings
+}
+
+/// Decompresses the `len_in` bytes at `str_in` into `output` and returns the
+/// decompressed length. If it is larger than `size`, only the first `size`
+/// bytes are written.
+///
+/// # Safety
+///
+/// `decoder` must be valid for reads, `str_in` for reads of `len_in` bytes
+/// and `output` for writes of `size` bytes; either may be null if its length
+/// is 0.
+#[no_mangle]
+pub unsafe extern "C" fn fsst_decompress(
+    decoder: *const FsstDecoder,
+    len_in: usize,
+    str_in: *const u8,
+    size: usize,
+    output: *mut u8,
+) -> usize {
+    let decoder = unsafe { &*decoder };
+    let codes = unsafe { raw_slice(str_in, len_in) };
+    let mut pos = 0;
+    let mut i = 0;
+
+    while i < codes.len() {
+        let code = codes[i] as usize;
+
+        if code != 255 {
+            let len = decoder.len[code] as usize;
+
+            if pos + 8 <= size {
+                unsafe {
+                    (output.add(pos) as *mut u64).write_unaligned(decoder.symbol[code]);
+                }
+            } else if pos < size {
+                let bytes = decoder.symbol[code].to_le_bytes();
+                let n = len.min(size - pos);
+
+                unsafe {
+                    ptr::copy_nonoverlapping(bytes.as_ptr(), output.add(pos), n);
+                }
+            }
+
+            pos += len;
+            i += 1;
+        } else if i + 1 < codes.len() {
+            if pos < size {
+                unsafe {
+                    output.add(pos).write(codes[i + 1]);
+                }
+            }
+
+            pos += 1;
+            i += 2;
+        } else {
+            break;
+        }
+    }
+
+    pos
+}
+
+/// # Safety
+///
+/// `len_in` and `str_in` must point to `n` lengths and `n` pointers to
+/// strings of those lengths. They may be null if `n` is 0.
+unsafe fn strings<'a>(
+    n: usize,
+    len_in: *const usize,
+    str_in: *const *const u8,
+) -> impl Iterator<Item = &'a [u8]> {
+    let (lengths, pointers) = unsafe { (raw_slice(len_in, n), raw_slice(str_in, n)) };
+
+    lengths
+        .iter()
+        .zip(pointers)
+        .map(|(&len, &p)| unsafe { raw_slice(p, len) })
+}
+
+/// Like [`slice::from_raw_parts`], but C callers may pass a null pointer
+/// for an empty array.
+///
+/// # Safety
+///
+/// `p` must be valid for reads of `len` elements if `len` is not 0.
+unsafe fn raw_slice<'a, T>(p: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(p, len) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read("tests.nosync/cwida/urls2")?;
+        let lines = file.split(|&b| b == b'\n').collect::<Vec<_>>();
+        let lengths = lines.iter().map(|line| line.len()).collect::<Vec<_>>();
+        let pointers = lines.iter().map(|line| line.as_ptr()).collect::<Vec<_>>();
+
+        let mut output = vec![0; 2 * file.len() + 7];
+        let mut len_out = vec![0; lines.len()];
+        let mut str_out = vec![ptr::null_mut(); lines.len()];
+        let mut exported = [0; FSST_MAXHEADER];
+        let mut imported = unsafe { std::mem::zeroed() };
+
+        unsafe {
+            let encoder = fsst_create(lines.len(), lengths.as_ptr(), pointers.as_ptr(), 0);
+            let copy = fsst_duplicate(encoder);
+            fsst_destroy(encoder);
+
+            let compressed = fsst_compress(
+                copy,
+                lines.len(),
+                lengths.as_ptr(),
+                pointers.as_ptr(),
+                output.len(),
+                output.as_mut_ptr(),
+                len_out.as_mut_ptr(),
+                str_out.as_mut_ptr(),
+            );
+            assert_eq!(compressed, lines.len());
+
+            // Stops at the first string that does not fit
+            let fits = fsst_compress(
+                copy,
+                lines.len(),
+                lengths.as_ptr(),
+                pointers.as_ptr(),
+                len_out[0] + len_out[1] + 1,
+                output.as_mut_ptr(),
+                len_out.as_mut_ptr(),
+                str_out.as_mut_ptr(),
+            );
+            assert_eq!(fits, 2);
+
+            let len = fsst_export(copy, exported.as_mut_ptr());
+            assert_eq!(fsst_import(&mut imported, exported.as_ptr()), len);
+
+            let decoder = fsst_decoder(copy);
+            fsst_destroy(copy);
+
+            let mut buffer = vec![0; 4096];
+            for (i, line) in lines.iter().enumerate() {
+                for decoder in [&decoder, &imported] {
+                    let len = fsst_decompress(
+                        decoder,
+                        len_out[i],
+                        str_out[i],
+                        buffer.len(),
+                        buffer.as_mut_ptr(),
+                    );
+                    assert_eq!(&buffer[..len], *line);
+                }
+
+                // Reports the full length and writes only `size` bytes
+                let size = line.len() / 2;
+                buffer[size] = 0xAA;
+                let len =
+                    fsst_decompress(&decoder, len_out[i], str_out[i], size, buffer.as_mut_ptr());
+                assert_eq!(len, line.len());
+                assert_eq!(buffer[..size], line[..size]);
+                assert_eq!(buffer[size], 0xAA);
+            }
+
+            assert_eq!(fsst_import(&mut imported, b"not a table".as_ptr()), 0);
+
+            // A histogram announcing more symbols than a table holds is
+            // rejected without reading past the header
+            let mut header = [255; CWIDA_HEADER];
+            header[..8].copy_from_slice(&exported[..8]);
+            header[8] = 0;
+            assert_eq!(fsst_import(&mut imported, header.as_ptr()), 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn zero_terminated() {
+        let lines: [&[u8]; 3] = [b"http://a.com/\0", b"http://b\0.com/\0", b"http://c.com"];
+        let lengths = lines.map(|line| line.len());
+        let pointers = lines.map(|line| line.as_ptr());
+
+        let mut output = [0; 128];
+        let mut len_out = [0; 3];
+        let mut str_out = [ptr::null_mut(); 3];
+        let mut buffer = [0; 64];
+
+        unsafe {
+            let encoder = fsst_create(3, lengths.as_ptr(), pointers.as_ptr(), 1);
+            fsst_compress(
+                encoder,
+                3,
+                lengths.as_ptr(),
+                pointers.as_ptr(),
+                output.len(),
+                output.as_mut_ptr(),
+                len_out.as_mut_ptr(),
+                str_out.as_mut_ptr(),
+            );
+
+            let decoder = fsst_decoder(encoder);
+            assert_eq!(decoder.zero_terminated, 1);
+
+            // The tiny table leaves codes unused; they decode to "corrupt"
+            assert_eq!(decoder.len[254], 8);
+            let len = fsst_decompress(&decoder, 1, [254].as_ptr(), 64, buffer.as_mut_ptr());
+            assert_eq!(&buffer[..len], b"corrupt\0");
+
+            // The terminator is kept if and only if the input had one
+            assert_eq!(*str_out[0].add(len_out[0] - 1), 0);
+            assert_ne!(*str_out[2].add(len_out[2] - 1), 0);
+
+            // Both decoders keep embedded zeros; only the Rust one drops the
+            // terminator, which the C input included
+            let mut decoded = Vec::new();
+            for (i, line) in lines.iter().enumerate() {
+                let len =
+                    fsst_decompress(&decoder, len_out[i], str_out[i], 64, buffer.as_mut_ptr());
+                assert_eq!(&buffer[..len], *line);
+
+                (*encoder).decode(slice::from_raw_parts(str_out[i], len_out[i]), &mut decoded);
+                assert_eq!(decoded, line.strip_suffix(b"\0").unwrap_or(line));
+            }
+
+            fsst_destroy(encoder);
+        }
+    }
+
+    #[test]
+    fn header_declares_exports() {
+        let header = include_str!("../include/fsst.h");
+        let exports = include_str!("ffi.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("pub unsafe extern \"C\" fn "))
+            .map(|line| &line[..line.find('(').unwrap()])
+            .collect::<Vec<_>>();
+
+        assert_eq!(exports.len(), 8);
+        for name in exports {
+            assert!(
+                header.contains(&format!("\n{name}(")),
+                "{name} is not declared"
+            );
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Rust implementation of the [FSST](https://github.com/cwida/fsst) static
+//! string compressor.
+//!
+//! FSST (Fast Static Symbol Table) replaces frequently occurring substrings
+//! of up to 8 bytes with 1-byte codes. A [`SymbolTable`] is trained once on a
+//! sample of the data and can then be used to compress and decompress each
+//! string independently:
+//!
+//! ```
+//! use fsst::SymbolTable;
+//!
+//! let lines: Vec<&[u8]> = vec![b"http://example.com/a", b"http://example.com/b"];
+//! let st = SymbolTable::build(&lines);
+//!
+//! let mut compressed = Vec::new();
+//! st.encode(lines[0], &mut compressed);
+//!
+//! let mut decompressed = Vec::new();
+//! st.decode(&compressed, &mut decompressed);
+//! assert_eq!(decompressed, lines[0]);
+//! ```
+
+mod batch;
+mod builder;
+mod column;
+mod container;
+mod counters;
+mod decoder;
+mod encoder;
+mod error;
+mod ffi;
+mod heap;
+mod lossy_pht;
+mod records;
+mod search;
+#[cfg(target_arch = "x86_64")]
+mod simd;
+mod stream;
+mod symbol;
+mod table;
+mod trainer;
+mod varint;
+
+pub use builder::SymbolTableBuilder;
+pub use column::CompressedColumn;
+pub use container::{ContainerReader, ContainerWriter};
+pub use decoder::Decoder;
+pub use encoder::Encoder;
+pub use error::{DecodeError, ImportError};
+pub use records::RecordFormat;
+pub use search::{MultiPatternMatcher, PrefixMatcher, SubstringMatcher};
+pub use stream::{FsstReader, FsstWriter, Records};
+pub use symbol::Symbol;
+pub use table::SymbolTable;
@@ -1,4 +1,4 @@
-const HASH_TABLE_SIZE: usize = 2048;
+pub(crate) const HASH_TABLE_SIZE: usize = 2048;
 
 #[derive(Clone, Copy)]
 pub struct TableEntry {
@@ -1,30 +1,135 @@
-use std::io::Write;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::time::Duration;
-use std::{env, fs, time::Instant};
+use std::{env, fs, process, time::Instant};
 
-mod counters;
-mod heap;
-mod lossy_pht;
-mod symbol;
-mod table;
-use table::SymbolTable;
+use fsst::{CompressedColumn, RecordFormat, SymbolTable};
+
+const USAGE: &str = "Usage: fsst compress|decompress [options] <file_in> <file_out>
+       fsst bench [options] <file_in> [file_out]
+
+Options:
+    -s, --separator <format>  how records are split: newline (default), nul,
+                              byte:N, fixed:N or length-prefixed
+    -t, --threads <n>         number of compression threads (default 1)";
 
 /*
-    Compression: cargo run --release file_in file_out
+    Compression:    cargo run --release compress file_in file_out
+    Decompression:  cargo run --release decompress file_in file_out
+    Benchmark:      cargo run --release bench file_in [file_out]
 */
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = env::args().collect();
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match (args.command.as_str(), &args.paths[..]) {
+        ("compress", [path_in, path_out]) => compress(path_in, path_out, args.format, args.threads),
+        ("decompress", [path_in, path_out]) => decompress(path_in, path_out),
+        ("bench", [path_in]) => bench(path_in, None, args.format),
+        ("bench", [path_in, path_out]) => bench(path_in, Some(path_out), args.format),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+struct Args {
+    command: String,
+    paths: Vec<String>,
+    format: RecordFormat,
+    threads: usize,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let command = args.next().ok_or("missing command")?;
+        let mut paths = Vec::new();
+        let mut format = RecordFormat::default();
+        let mut threads = 1;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-s" | "--separator" => {
+                    format = args.next().ok_or("missing record format")?.parse()?;
+                }
+                "-t" | "--threads" => {
+                    threads = args
+                        .next()
+                        .ok_or("missing number of threads")?
+                        .parse()
+                        .map_err(|_| "invalid number of threads")?;
+                }
+                _ => paths.push(arg),
+            }
+        }
+
+        Ok(Self {
+            command,
+            paths,
+            format,
+            threads,
+        })
+    }
+}
+
+fn compress(
+    path_in: &str,
+    path_out: &str,
+    format: RecordFormat,
+    threads: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let file = fs::read(path_in)?;
+    let records = format.split(&file)?;
+
+    let column = CompressedColumn::compress_parallel(&records, threads);
+
+    let mut output = BufWriter::new(fs::File::create(path_out)?);
+    format.write_to(&mut output)?;
+    column.write_to(&mut output)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+fn decompress(path_in: &str, path_out: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut input = BufReader::new(fs::File::open(path_in)?);
+    let format = RecordFormat::read_from(&mut input)?;
+    let column = CompressedColumn::read_from(&mut input)?;
+
+    if input.read(&mut [0])? != 0 {
+        return Err("trailing data after compressed column".into());
+    }
+
+    let mut buffer = Vec::new();
+    let mut offsets = Vec::new();
+    column.decompress(&mut buffer, &mut offsets);
+
+    let records = offsets
+        .windows(2)
+        .map(|w| &buffer[w[0] as usize..w[1] as usize]);
+
+    let mut output = BufWriter::new(fs::File::create(path_out)?);
+    format.join(records, &mut output)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+fn bench(
+    path_in: &str,
+    path_out: Option<&str>,
+    format: RecordFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut start;
     let mut end;
 
-    let file = fs::read_to_string(&args[1])?;
-    let mut output = fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&args[2])?;
-
-    let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+    let file = fs::read(path_in)?;
+    let lines = format.split(&file)?;
 
     start = Instant::now();
     let st = SymbolTable::build(&lines);
@@ -38,17 +143,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut size = 0;
     let mut buffer = Vec::with_capacity(8 * 1024 * 1024);
     let mut buffer_out = Vec::with_capacity(file.len());
+    let mut decoded = Vec::with_capacity(lines.len());
     for line in &lines {
         start = Instant::now();
         st.encode(line, &mut buffer);
         end += Instant::now().duration_since(start);
         size += buffer.len();
-        st.decode(&buffer, &mut buffer_out);
-        writeln!(output, "{}", String::from_utf8(buffer_out.to_vec())?)?;
+        if path_out.is_some() {
+            st.decode(&buffer, &mut buffer_out);
+            decoded.push(buffer_out.clone());
+        }
         buffer.clear();
         buffer_out.clear();
     }
 
+    if let Some(path) = path_out {
+        let mut output = BufWriter::new(fs::File::create(path)?);
+        format.join(decoded.iter().map(Vec::as_slice), &mut output)?;
+        output.flush()?;
+    }
+
     println!(
         "Compression speed: {} MB/s",
         (file.len() as f64 / 1024. / 1024.) / end.as_secs_f64()
@@ -64,6 +178,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 #[cfg(test)]
 mod tests {
+    use std::path::{Path, PathBuf};
+    use std::process;
     use std::time::Duration;
 
     use super::*;
@@ -81,8 +197,8 @@ mod tests {
             let tests_unw = tests.unwrap();
             let filename = &tests_unw.file_name().into_string().unwrap();
             println!("File: {}", filename);
-            results.push_str(&filename);
-            results.push_str("|");
+            results.push_str(filename);
+            results.push('|');
 
             let file = fs::read_to_string(tests_unw.path())?;
 
@@ -92,7 +208,7 @@ mod tests {
             let st = SymbolTable::build(&lines);
             end = Instant::now().duration_since(start);
             results.push_str(&(file.len() as f64 / 1024. / 1024. / end.as_secs_f64()).to_string());
-            results.push_str("|");
+            results.push('|');
 
             let mut size = 0;
             let mut buffer = Vec::with_capacity(8 * 1024 * 1024);
@@ -129,7 +245,7 @@ mod tests {
             let tests_unw = tests.unwrap();
             let filename = &tests_unw.file_name().into_string().unwrap();
             println!("File: {}", filename);
-            results.push_str(&filename);
+            results.push_str(filename);
             results.push('|');
 
             let file = fs::read_to_string(tests_unw.path())?;
@@ -162,4 +278,66 @@ mod tests {
 
         Ok(())
     }
+
+    /// Returns a path in the temporary directory that is unique to this test
+    /// process, so concurrent runs do not overwrite each other's files.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("fsst-{}-{}", process::id(), name))
+    }
+
+    fn compress_decompress(
+        path: &Path,
+        format: RecordFormat,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let compressed = temp_path(&format!("{}.fsst", filename));
+        let decompressed = temp_path(&format!("{}.out", filename));
+
+        compress(
+            path.to_str().unwrap(),
+            compressed.to_str().unwrap(),
+            format,
+            4,
+        )?;
+        decompress(compressed.to_str().unwrap(), decompressed.to_str().unwrap())?;
+
+        assert_eq!(fs::read(path)?, fs::read(&decompressed)?);
+
+        fs::remove_file(compressed)?;
+        fs::remove_file(decompressed)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn compress_decompress_files() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = fs::read_dir("tests.nosync/cwida")?;
+
+        for tests in dir {
+            let tests_unw = tests.unwrap();
+            compress_decompress(&tests_unw.path(), RecordFormat::default())?;
+        }
+
+        for filename in ["genome", "hex", "japanese"] {
+            let path = Path::new("tests.nosync/cwida").join(filename);
+            compress_decompress(&path, RecordFormat::FixedWidth(37))?;
+            compress_decompress(&path, RecordFormat::Delimited(0))?;
+        }
+
+        // Binary keys, with every byte value and runs of zeros
+        let mut binary = Vec::new();
+        let mut rnd: u32 = 4637947;
+        for i in 0..200_000u32 {
+            rnd = rnd.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            binary.push(if i % 16 < 4 { 0 } else { (rnd >> 16) as u8 });
+        }
+
+        let path = temp_path("binary");
+        fs::write(&path, binary)?;
+        compress_decompress(&path, RecordFormat::FixedWidth(16))?;
+        compress_decompress(&path, RecordFormat::Delimited(b'\n'))?;
+        fs::remove_file(path)?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+use crate::error::{invalid_data, invalid_input};
+
+/// How a file is split into the records that are compressed one by one.
+///
+/// Splitting and joining are exact inverses, so any byte sequence is
+/// restored unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Records separated by a byte, such as `\n` or `\0`. The separator is not
+    /// part of the records and a trailing separator yields an empty last
+    /// record.
+    Delimited(u8),
+    /// Records of a fixed number of bytes; the last one may be shorter.
+    FixedWidth(usize),
+    /// Records preceded by their length as a little-endian `u32`.
+    LengthPrefixed,
+}
+
+impl Default for RecordFormat {
+    fn default() -> Self {
+        Self::Delimited(b'\n')
+    }
+}
+
+impl RecordFormat {
+    /// Splits `data` into records.
+    pub fn split<'a>(&self, data: &'a [u8]) -> io::Result<Vec<&'a [u8]>> {
+        match *self {
+            Self::Delimited(separator) => Ok(data.split(|&c| c == separator).collect()),
+            Self::FixedWidth(0) => Err(invalid_input("record width must not be zero")),
+            Self::FixedWidth(width) => Ok(data.chunks(width).collect()),
+            Self::LengthPrefixed => {
+                let mut records = Vec::new();
+                let mut rest = data;
+
+                while !rest.is_empty() {
+                    let len = rest
+                        .get(..4)
+                        .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+                        .ok_or_else(|| invalid_input("truncated record length"))?;
+
+                    if rest.len() - 4 < len {
+                        return Err(invalid_input("truncated record"));
+                    }
+
+                    records.push(&rest[4..4 + len]);
+                    rest = &rest[4 + len..];
+                }
+
+                Ok(records)
+            }
+        }
+    }
+
+    /// Writes `records` back in this format, the inverse of
+    /// [`RecordFormat::split`].
+    pub fn join<'a, W: Write>(
+        &self,
+        records: impl IntoIterator<Item = &'a [u8]>,
+        mut writer: W,
+    ) -> io::Result<()> {
+        for (i, record) in records.into_iter().enumerate() {
+            match *self {
+                Self::Delimited(separator) => {
+                    if i > 0 {
+                        writer.write_all(&[separator])?;
+                    }
+                }
+                Self::FixedWidth(_) => {}
+                Self::LengthPrefixed => {
+                    let len = u32::try_from(record.len())
+                        .map_err(|_| invalid_input("record longer than 4 GiB"))?;
+                    writer.write_all(&len.to_le_bytes())?;
+                }
+            }
+
+            writer.write_all(record)?;
+        }
+
+        Ok(())
+    }
+
+    /// Serializes the format as a tag byte followed by its parameter.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        match *self {
+            Self::Delimited(separator) => writer.write_all(&[0, separator]),
+            Self::FixedWidth(width) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&(width as u64).to_le_bytes())
+            }
+            Self::LengthPrefixed => writer.write_all(&[2]),
+        }
+    }
+
+    /// Reads a format written by [`RecordFormat::write_to`].
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut tag = [0];
+        reader.read_exact(&mut tag)?;
+
+        match tag[0] {
+            0 => {
+                reader.read_exact(&mut tag)?;
+                Ok(Self::Delimited(tag[0]))
+            }
+            1 => {
+                let mut width = [0; 8];
+                reader.read_exact(&mut width)?;
+                usize::try_from(u64::from_le_bytes(width))
+                    .map(Self::FixedWidth)
+                    .map_err(|_| invalid_data("record width too large"))
+            }
+            2 => Ok(Self::LengthPrefixed),
+            _ => Err(invalid_data("unknown record format")),
+        }
+    }
+}
+
+/// Parses `newline`, `nul`, `byte:N`, `fixed:N` or `length-prefixed`.
+impl FromStr for RecordFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_err = |_| format!("invalid record format: {}", s);
+
+        match s.split_once(':') {
+            None if s == "newline" => Ok(Self::Delimited(b'\n')),
+            None if s == "nul" => Ok(Self::Delimited(0)),
+            None if s == "length-prefixed" => Ok(Self::LengthPrefixed),
+            Some(("byte", byte)) => byte.parse().map(Self::Delimited).map_err(parse_err),
+            Some(("fixed", width)) => match width.parse().map_err(parse_err)? {
+                0 => Err(format!("invalid record format: {}", s)),
+                width => Ok(Self::FixedWidth(width)),
+            },
+            _ => Err(format!("invalid record format: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for RecordFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Delimited(b'\n') => write!(f, "newline"),
+            Self::Delimited(0) => write!(f, "nul"),
+            Self::Delimited(byte) => write!(f, "byte:{}", byte),
+            Self::FixedWidth(width) => write!(f, "fixed:{}", width),
+            Self::LengthPrefixed => write!(f, "length-prefixed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_join() -> io::Result<()> {
+        let mut prefixed = Vec::new();
+        RecordFormat::LengthPrefixed.join([&b"ab"[..], b"", b"\n\0c"], &mut prefixed)?;
+
+        let cases: [(RecordFormat, &[u8]); 5] = [
+            (RecordFormat::Delimited(b'\n'), b"a\r\nb\n\nc\n"),
+            (RecordFormat::Delimited(0), b"a\0\xff\xfe\0"),
+            (RecordFormat::FixedWidth(3), b"abcdefgh"),
+            (RecordFormat::FixedWidth(3), b""),
+            (RecordFormat::LengthPrefixed, &prefixed),
+        ];
+
+        for (format, data) in cases {
+            let records = format.split(data)?;
+            let mut joined = Vec::new();
+            format.join(records, &mut joined)?;
+            assert_eq!(joined, data);
+
+            let mut serialized = Vec::new();
+            format.write_to(&mut serialized)?;
+            assert_eq!(RecordFormat::read_from(&serialized[..])?, format);
+            assert_eq!(format.to_string().parse(), Ok(format));
+        }
+
+        assert_eq!(RecordFormat::LengthPrefixed.split(&prefixed)?.len(), 3);
+        assert!(RecordFormat::LengthPrefixed
+            .split(&prefixed[..prefixed.len() - 1])
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,597 @@
+//! Predicates evaluated directly on compressed strings.
+
+use std::collections::VecDeque;
+
+use crate::decoder::{strip_terminator, Decoder};
+use crate::table::SymbolTable;
+
+/// Matches the strings starting with a prefix, like `LIKE 'prefix%'`.
+///
+/// The bytes of each symbol are compared with the prefix as a whole word, and
+/// the walk over the codes stops at the first mismatch or as soon as the
+/// prefix is covered, so the rest of the string is never decoded.
+#[derive(Clone)]
+pub struct PrefixMatcher {
+    decoder: Decoder,
+    /// The prefix followed by 8 zero bytes, so a word can be read at any
+    /// position.
+    prefix: Vec<u8>,
+    len: usize,
+}
+
+impl PrefixMatcher {
+    /// Builds a matcher for `prefix` over strings compressed with `table`.
+    pub fn new(table: &SymbolTable, prefix: &[u8]) -> Self {
+        let mut padded = prefix.to_vec();
+        padded.extend_from_slice(&[0; 8]);
+
+        Self {
+            decoder: table.decoder().clone(),
+            prefix: padded,
+            len: prefix.len(),
+        }
+    }
+
+    /// Returns whether the compressed `string` starts with the prefix.
+    pub fn matches(&self, string: &[u8]) -> bool {
+        let string = self.decoder.codes(string);
+        let mut pos = 0;
+        let mut i = 0;
+
+        while pos < self.len {
+            let Some(&code) = string.get(i) else {
+                return false;
+            };
+
+            let (value, len) = if code != 255 {
+                i += 1;
+                (
+                    self.decoder.values[code as usize],
+                    self.decoder.lengths[code as usize] as usize,
+                )
+            } else {
+                let Some(&byte) = string.get(i + 1) else {
+                    return false;
+                };
+                i += 2;
+                (byte as u64, 1)
+            };
+
+            if len == 0 {
+                return false;
+            }
+
+            // Only the bytes of the symbol that overlap the prefix count
+            let covered = len.min(self.len - pos);
+            let word = u64::from_le_bytes(self.prefix[pos..pos + 8].try_into().unwrap());
+
+            if (value ^ word) & (u64::MAX >> (64 - 8 * covered)) != 0 {
+                return false;
+            }
+
+            pos += len;
+        }
+
+        true
+    }
+
+    /// Returns the rows of the compressed strings in `data` that start with
+    /// the prefix, in order. String `i` is `data[offsets[i]..offsets[i + 1]]`,
+    /// as written by [`SymbolTable::encode_batch`].
+    pub fn filter(&self, data: &[u8], offsets: &[u64]) -> Vec<usize> {
+        filter(data, offsets, |string| self.matches(string))
+    }
+}
+
+/// Matches the strings containing a substring, like `LIKE '%needle%'`.
+///
+/// The KMP automaton of the needle is lifted from bytes to codes: for every
+/// state and code, the state after all bytes of the symbol is precomputed,
+/// so the search costs one table lookup per compressed byte. Matching is
+/// absorbing, which lets the search stop at the first occurrence.
+#[derive(Clone)]
+pub struct SubstringMatcher {
+    /// Next state for each state and byte, at `256 * state + byte`; used for
+    /// escaped bytes.
+    bytes: Box<[u32]>,
+    /// Next state for each state and code, at `256 * state + code`.
+    codes: Box<[u32]>,
+    /// The state reached once the needle has been seen.
+    accept: u32,
+    zero_terminated: bool,
+}
+
+impl SubstringMatcher {
+    /// Builds a matcher for `needle` over strings compressed with `table`.
+    pub fn new(table: &SymbolTable, needle: &[u8]) -> Self {
+        let accept = needle.len();
+        let mut bytes = vec![0; 256 * (accept + 1)].into_boxed_slice();
+
+        // KMP: a mismatch in state `j` continues from the state `restart`
+        // reached on the needle without its first byte
+        if let Some(&first) = needle.first() {
+            bytes[first as usize] = 1;
+        }
+        let mut restart = 0;
+        for j in 1..accept {
+            bytes.copy_within(256 * restart..256 * restart + 256, 256 * j);
+            bytes[256 * j + needle[j] as usize] = j as u32 + 1;
+            restart = bytes[256 * restart + needle[j] as usize] as usize;
+        }
+        bytes[256 * accept..].fill(accept as u32);
+
+        let decoder = table.decoder();
+        let mut codes = vec![0; bytes.len()].into_boxed_slice();
+
+        for state in 0..accept {
+            for code in 0..255 {
+                let len = decoder.lengths[code] as usize;
+                let symbol = &decoder.values[code].to_le_bytes()[..len];
+
+                codes[256 * state + code] = symbol
+                    .iter()
+                    .fold(state as u32, |s, &b| bytes[256 * s as usize + b as usize]);
+            }
+        }
+        codes[256 * accept..].fill(accept as u32);
+
+        Self {
+            bytes,
+            codes,
+            accept: accept as u32,
+            zero_terminated: table.is_zero_terminated(),
+        }
+    }
+
+    /// Returns whether the compressed `string` contains the needle.
+    pub fn matches(&self, string: &[u8]) -> bool {
+        let string = strip_terminator(string, self.zero_terminated);
+        let mut state = 0;
+        let mut i = 0;
+
+        while state < self.accept && i < string.len() {
+            let code = string[i];
+
+            if code != 255 {
+                state = self.codes[256 * state as usize + code as usize];
+                i += 1;
+            } else if let Some(&byte) = string.get(i + 1) {
+                state = self.bytes[256 * state as usize + byte as usize];
+                i += 2;
+            } else {
+                break;
+            }
+        }
+
+        state == self.accept
+    }
+
+    /// Returns the rows of the compressed strings in `data` that contain the
+    /// needle, in order, like [`PrefixMatcher::filter`].
+    pub fn filter(&self, data: &[u8], offsets: &[u64]) -> Vec<usize> {
+        filter(data, offsets, |string| self.matches(string))
+    }
+}
+
+/// Finds which of a set of patterns occur in compressed strings.
+///
+/// Like [`SubstringMatcher`], with the Aho-Corasick automaton of the patterns
+/// lifted to codes. Transitions whose symbol passes through a state where a
+/// pattern ends are flagged; only those walk the bytes of the symbol again to
+/// collect the patterns, so a search is one table lookup per compressed byte
+/// however many patterns there are.
+#[derive(Clone)]
+pub struct MultiPatternMatcher {
+    decoder: Decoder,
+    /// Next state for each state and byte, at `256 * state + byte`, with
+    /// [`HIT`] set if a pattern ends there.
+    bytes: Box<[u32]>,
+    /// Next state for each state and code, at `256 * state + code`, with
+    /// [`HIT`] set if a pattern ends within the symbol.
+    codes: Box<[u32]>,
+    /// Patterns ending in state `s`, `outputs[output_offsets[s]..output_offsets[s + 1]]`.
+    /// Empty patterns end in the root and are reported for every string.
+    outputs: Vec<u32>,
+    output_offsets: Vec<u32>,
+    patterns: usize,
+}
+
+/// Flag of a transition through a state where a pattern ends.
+const HIT: u32 = 1 << 31;
+
+impl MultiPatternMatcher {
+    /// Builds a matcher for `patterns` over strings compressed with `table`.
+    pub fn new(table: &SymbolTable, patterns: &[&[u8]]) -> Self {
+        const NONE: u32 = u32::MAX;
+
+        // Trie of the patterns, extended to a full automaton below
+        let mut bytes = vec![NONE; 256];
+        let mut outputs = vec![Vec::new()];
+
+        for (id, pattern) in patterns.iter().enumerate() {
+            let mut state = 0;
+
+            for &b in *pattern {
+                if bytes[256 * state + b as usize] == NONE {
+                    bytes[256 * state + b as usize] = outputs.len() as u32;
+                    bytes.extend_from_slice(&[NONE; 256]);
+                    outputs.push(Vec::new());
+                }
+                state = bytes[256 * state + b as usize] as usize;
+            }
+
+            outputs[state].push(id as u32);
+        }
+
+        // Breadth-first, so the failure state of every state is complete
+        // before the state itself. Outputs of the root are left out of the
+        // other states, or every transition would be a hit.
+        let mut fail = vec![0; outputs.len()];
+        let mut queue = VecDeque::new();
+
+        for next in &mut bytes[..256] {
+            match *next {
+                NONE => *next = 0,
+                child => queue.push_back(child as usize),
+            }
+        }
+
+        while let Some(state) = queue.pop_front() {
+            for b in 0..256 {
+                let fallback = bytes[256 * fail[state] + b];
+
+                match bytes[256 * state + b] {
+                    NONE => bytes[256 * state + b] = fallback,
+                    child => {
+                        let child = child as usize;
+                        fail[child] = fallback as usize;
+
+                        if fallback != 0 {
+                            let inherited = outputs[fallback as usize].clone();
+                            outputs[child].extend(inherited);
+                        }
+
+                        queue.push_back(child);
+                    }
+                }
+            }
+        }
+
+        let states = outputs.len();
+        let hit = |state: u32| state != 0 && !outputs[state as usize].is_empty();
+        let decoder = table.decoder();
+        let mut codes = vec![0; bytes.len()].into_boxed_slice();
+
+        for state in 0..states {
+            for code in 0..255 {
+                let len = decoder.lengths[code] as usize;
+                let mut next = state as u32;
+                let mut flags = 0;
+
+                for &b in &decoder.values[code].to_le_bytes()[..len] {
+                    next = bytes[256 * next as usize + b as usize];
+                    flags |= if hit(next) { HIT } else { 0 };
+                }
+
+                codes[256 * state + code] = next | flags;
+            }
+        }
+
+        let bytes = bytes
+            .iter()
+            .map(|&next| next | if hit(next) { HIT } else { 0 })
+            .collect();
+
+        let mut output_offsets = vec![0];
+        for output in &outputs {
+            output_offsets.push(output_offsets[output_offsets.len() - 1] + output.len() as u32);
+        }
+
+        Self {
+            decoder: decoder.clone(),
+            bytes,
+            codes,
+            outputs: outputs.concat(),
+            output_offsets,
+            patterns: patterns.len(),
+        }
+    }
+
+    /// Sets bit `id % 64` of `found[id / 64]` for every pattern `id` occurring
+    /// in the compressed `string`.
+    fn find_bits(&self, string: &[u8], found: &mut [u64]) {
+        self.report(0, found);
+
+        let string = self.decoder.codes(string);
+        let mut state = 0;
+        let mut i = 0;
+
+        while i < string.len() {
+            let code = string[i];
+
+            let (next, symbol, len) = if code != 255 {
+                i += 1;
+                (
+                    self.codes[256 * state as usize + code as usize],
+                    self.decoder.values[code as usize],
+                    self.decoder.lengths[code as usize] as usize,
+                )
+            } else if let Some(&byte) = string.get(i + 1) {
+                i += 2;
+                (
+                    self.bytes[256 * state as usize + byte as usize],
+                    byte as u64,
+                    1,
+                )
+            } else {
+                break;
+            };
+
+            if next & HIT != 0 {
+                for &b in &symbol.to_le_bytes()[..len] {
+                    state = self.bytes[256 * state as usize + b as usize] & !HIT;
+                    self.report(state, found);
+                }
+            }
+
+            state = next & !HIT;
+        }
+    }
+
+    /// Sets the bits of the patterns ending in `state`.
+    #[inline]
+    fn report(&self, state: u32, found: &mut [u64]) {
+        let start = self.output_offsets[state as usize] as usize;
+        let end = self.output_offsets[state as usize + 1] as usize;
+
+        for &id in &self.outputs[start..end] {
+            found[id as usize / 64] |= 1 << (id % 64);
+        }
+    }
+
+    /// Writes the indices of the patterns occurring in the compressed
+    /// `string` to `found`, overwriting its contents, in increasing order.
+    pub fn find(&self, string: &[u8], found: &mut Vec<usize>) {
+        let mut bits = vec![0; self.patterns.div_ceil(64)];
+
+        found.clear();
+        self.find_bits(string, &mut bits);
+        push_set_bits(&bits, found);
+    }
+
+    /// Finds the patterns of every compressed string in `data`, like
+    /// [`MultiPatternMatcher::find`]. The patterns occurring in string `i`,
+    /// `data[offsets[i]..offsets[i + 1]]`, are written to
+    /// `found[found_offsets[i]..found_offsets[i + 1]]`.
+    pub fn find_batch(
+        &self,
+        data: &[u8],
+        offsets: &[u64],
+        found: &mut Vec<usize>,
+        found_offsets: &mut Vec<u64>,
+    ) {
+        let mut bits = vec![0; self.patterns.div_ceil(64)];
+
+        found.clear();
+        found_offsets.clear();
+        found_offsets.reserve(offsets.len());
+        found_offsets.push(0);
+
+        for w in offsets.windows(2) {
+            bits.fill(0);
+            self.find_bits(&data[w[0] as usize..w[1] as usize], &mut bits);
+            push_set_bits(&bits, found);
+            found_offsets.push(found.len() as u64);
+        }
+    }
+}
+
+/// Appends the index of every set bit of `bits` to `out`.
+fn push_set_bits(bits: &[u64], out: &mut Vec<usize>) {
+    for (word, &bits) in bits.iter().enumerate() {
+        let mut rest = bits;
+
+        while rest != 0 {
+            out.push(64 * word + rest.trailing_zeros() as usize);
+            rest &= rest - 1;
+        }
+    }
+}
+
+/// Returns the rows `i` for which `matches` holds on
+/// `data[offsets[i]..offsets[i + 1]]`.
+fn filter(data: &[u8], offsets: &[u64], matches: impl Fn(&[u8]) -> bool) -> Vec<usize> {
+    offsets
+        .windows(2)
+        .enumerate()
+        .filter(|(_, w)| matches(&data[w[0] as usize..w[1] as usize]))
+        .map(|(row, _)| row)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::SymbolTableBuilder;
+
+    #[test]
+    fn prefix() -> Result<(), Box<dyn std::error::Error>> {
+        for corpus in ["urls2", "location"] {
+            let file = fs::read(format!("tests.nosync/cwida/{corpus}"))?;
+            let lines = file.split(|&b| b == b'\n').collect::<Vec<_>>();
+            let st = SymbolTable::build(&lines);
+
+            let mut data = Vec::new();
+            let mut offsets = Vec::new();
+            st.encode_batch(&lines, &mut data, &mut offsets);
+
+            let prefixes: [&[u8]; 6] = [
+                b"",
+                b"http://www.",
+                &lines[3][..lines[3].len() / 2],
+                lines[7],
+                &[lines[7], b"x"].concat(),
+                b"\xFFnot there",
+            ];
+
+            for prefix in prefixes {
+                let expected = (0..lines.len())
+                    .filter(|&row| lines[row].starts_with(prefix))
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    PrefixMatcher::new(&st, prefix).filter(&data, &offsets),
+                    expected
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_zero_terminated() {
+        let lines: [&[u8]; 4] = [b"abcabcabc", b"abcab", b"abd", b"ab"];
+        let st = SymbolTableBuilder::new()
+            .zero_terminated(true)
+            .build(&lines);
+
+        let mut compressed = Vec::new();
+        st.encode(b"ab\0c", &mut compressed);
+
+        // An embedded 0 is part of the string, the terminator is not
+        assert!(PrefixMatcher::new(&st, b"ab\0").matches(&compressed));
+        assert!(PrefixMatcher::new(&st, b"ab\0c").matches(&compressed));
+        assert!(!PrefixMatcher::new(&st, b"ab\0c\0").matches(&compressed));
+        assert!(SubstringMatcher::new(&st, b"\0c").matches(&compressed));
+        assert!(!SubstringMatcher::new(&st, b"c\0").matches(&compressed));
+    }
+
+    #[test]
+    fn substring() -> Result<(), Box<dyn std::error::Error>> {
+        for corpus in ["urls2", "l_comment", "chinese"] {
+            let file = fs::read(format!("tests.nosync/cwida/{corpus}"))?;
+            let lines = file.split(|&b| b == b'\n').collect::<Vec<_>>();
+            let st = SymbolTable::build(&lines);
+
+            let mut data = Vec::new();
+            let mut offsets = Vec::new();
+            st.encode_batch(&lines, &mut data, &mut offsets);
+
+            let needles: [&[u8]; 6] = [
+                b"",
+                b"com",
+                b"the",
+                &lines[3][lines[3].len() / 3..lines[3].len() / 2],
+                lines[7],
+                b"\xFFnot there",
+            ];
+
+            for needle in needles {
+                let expected = (0..lines.len())
+                    .filter(|&row| contains(lines[row], needle))
+                    .collect::<Vec<_>>();
+
+                assert_eq!(
+                    SubstringMatcher::new(&st, needle).filter(&data, &offsets),
+                    expected
+                );
+            }
+        }
+
+        // Partial matches that overlap the real one, within and across symbols
+        let lines: [&[u8]; 3] = [b"aaaab", b"abaabab", b"aabaab"];
+        let st = SymbolTable::build(&lines);
+        let mut compressed = Vec::new();
+
+        for needle in [&b"aaab"[..], b"abab", b"aabaab", b"abaa"] {
+            for line in lines {
+                compressed.clear();
+                st.encode(line, &mut compressed);
+                assert_eq!(
+                    SubstringMatcher::new(&st, needle).matches(&compressed),
+                    contains(line, needle)
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn multi_pattern() -> Result<(), Box<dyn std::error::Error>> {
+        for corpus in ["urls2", "l_comment"] {
+            let file = fs::read(format!("tests.nosync/cwida/{corpus}"))?;
+            let lines = file.split(|&b| b == b'\n').collect::<Vec<_>>();
+            let st = SymbolTable::build(&lines);
+
+            let mut data = Vec::new();
+            let mut offsets = Vec::new();
+            st.encode_batch(&lines, &mut data, &mut offsets);
+
+            let patterns: [&[u8]; 10] = [
+                b"http",
+                b"www",
+                b"com",
+                b".com",
+                b"com",
+                b"the",
+                b"",
+                &lines[3][lines[3].len() / 3..lines[3].len() / 2],
+                lines[7],
+                b"\xFFnot there",
+            ];
+            let matcher = MultiPatternMatcher::new(&st, &patterns);
+
+            let mut found = Vec::new();
+            let mut found_offsets = Vec::new();
+            matcher.find_batch(&data, &offsets, &mut found, &mut found_offsets);
+
+            for (row, line) in lines.iter().enumerate() {
+                let expected = (0..patterns.len())
+                    .filter(|&id| contains(line, patterns[id]))
+                    .collect::<Vec<_>>();
+                let range = found_offsets[row] as usize..found_offsets[row + 1] as usize;
+
+                assert_eq!(found[range], expected);
+            }
+        }
+
+        // Patterns that are suffixes of one another, and more than 64 of them
+        let lines: [&[u8]; 3] = [b"aaaab", b"abaabab", b"aabaab\0ab"];
+        let patterns = (0..100)
+            .map(|i| [&b"ab"[..], b"bab", b"aab", b"aa"][i % 4].repeat(i / 50 + 1))
+            .chain([b"\0a".to_vec(), b"b\0".to_vec(), b"ab\0".to_vec()])
+            .collect::<Vec<_>>();
+        let patterns = patterns.iter().map(|p| &p[..]).collect::<Vec<_>>();
+
+        for zero_terminated in [false, true] {
+            let st = SymbolTableBuilder::new()
+                .zero_terminated(zero_terminated)
+                .build(&lines);
+            let matcher = MultiPatternMatcher::new(&st, &patterns);
+            let mut compressed = Vec::new();
+            let mut found = Vec::new();
+
+            for line in lines {
+                compressed.clear();
+                st.encode(line, &mut compressed);
+                matcher.find(&compressed, &mut found);
+
+                let expected = (0..patterns.len())
+                    .filter(|&id| contains(line, patterns[id]))
+                    .collect::<Vec<_>>();
+
+                assert_eq!(found, expected);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
+    }
+}
@@ -0,0 +1,313 @@
+//! Vectorized bulk encoder for x86-64.
+//!
+//! Compressing one string is a chain of dependent lookups, so instead of
+//! vectorizing within a string, the batch is split into one group of strings
+//! per vector lane and every lane looks up its next symbol at the same time.
+//! Only the lookup runs in vector registers: writing the codes and finishing
+//! the last 7 bytes of each string stays scalar, which keeps the output
+//! byte-identical to [`Encoder::encode`].
+
+use std::arch::x86_64::*;
+
+use crate::batch::split_balanced;
+use crate::encoder::Encoder;
+use crate::lossy_pht::HASH_TABLE_SIZE;
+
+/// Multiplier of `lossy_pht::hash`.
+const MULTIPLIER: i64 = 2971215073;
+
+/// Compresses `inputs` like [`Encoder::encode_batch`] with the widest
+/// vector extension of the CPU. Returns `false`, leaving `out` and `offsets`
+/// untouched, if neither AVX-512 nor AVX2 is available.
+pub(crate) fn encode_batch(
+    encoder: &Encoder,
+    inputs: &[&[u8]],
+    out: &mut Vec<u8>,
+    offsets: &mut Vec<u64>,
+) -> bool {
+    if is_x86_feature_detected!("avx512f") {
+        unsafe { encode_batch_avx512(encoder, inputs, out, offsets) }
+    } else if is_x86_feature_detected!("avx2") {
+        unsafe { encode_batch_avx2(encoder, inputs, out, offsets) }
+    } else {
+        return false;
+    }
+
+    true
+}
+
+#[target_feature(enable = "avx2")]
+unsafe fn encode_batch_avx2(
+    encoder: &Encoder,
+    inputs: &[&[u8]],
+    out: &mut Vec<u8>,
+    offsets: &mut Vec<u64>,
+) {
+    encode_lanes(encoder, inputs, out, offsets, |words: &[u64; 4]| {
+        lookup_avx2(encoder, words)
+    });
+}
+
+#[target_feature(enable = "avx512f")]
+unsafe fn encode_batch_avx512(
+    encoder: &Encoder,
+    inputs: &[&[u8]],
+    out: &mut Vec<u8>,
+    offsets: &mut Vec<u64>,
+) {
+    encode_lanes(encoder, inputs, out, offsets, |words: &[u64; 8]| {
+        lookup_avx512(encoder, words)
+    });
+}
+
+/// Finds the longest symbol at the start of each of the 4 words, like
+/// `Encoder::find_longest_symbol`, and returns their `meta`.
+///
+/// The `u16` meta tables are gathered 4 bytes at a time, which also reads the
+/// next entry (or the padding entry at the end) into the upper half.
+#[target_feature(enable = "avx2")]
+unsafe fn lookup_avx2(encoder: &Encoder, words: &[u64; 4]) -> [u64; 4] {
+    let zero = _mm256_setzero_si256();
+    let low16 = _mm256_set1_epi64x(0xFF_FF);
+    let words = _mm256_loadu_si256(words.as_ptr() as *const __m256i);
+
+    // The first 3 bytes fit in 32 bits, so the hash needs no 64-bit multiply
+    let first3 = _mm256_and_si256(words, _mm256_set1_epi64x(0xFF_FF_FF));
+    let hashes = _mm256_xor_si256(
+        _mm256_mul_epu32(first3, _mm256_set1_epi64x(MULTIPLIER)),
+        _mm256_srli_epi64::<15>(first3),
+    );
+    let slots = _mm256_and_si256(hashes, _mm256_set1_epi64x(HASH_TABLE_SIZE as i64 - 1));
+    let values = _mm256_i64gather_epi64::<8>(encoder.hash_values.as_ptr() as *const i64, slots);
+    let hash_meta = _mm256_and_si256(
+        _mm256_cvtepu32_epi64(_mm256_i64gather_epi32::<2>(
+            encoder.hash_meta.as_ptr() as *const i32,
+            slots,
+        )),
+        low16,
+    );
+
+    let bits = _mm256_slli_epi64::<3>(_mm256_srli_epi64::<8>(hash_meta));
+    let masks = _mm256_srlv_epi64(
+        _mm256_set1_epi64x(-1),
+        _mm256_sub_epi64(_mm256_set1_epi64x(64), bits),
+    );
+    let mismatches = _mm256_and_si256(_mm256_xor_si256(words, values), masks);
+    let hits = _mm256_andnot_si256(
+        _mm256_cmpeq_epi64(hash_meta, zero),
+        _mm256_cmpeq_epi64(mismatches, zero),
+    );
+
+    let short_meta = _mm256_and_si256(
+        _mm256_cvtepu32_epi64(_mm256_i64gather_epi32::<2>(
+            encoder.short_meta.as_ptr() as *const i32,
+            _mm256_and_si256(words, low16),
+        )),
+        low16,
+    );
+
+    let mut meta = [0; 4];
+    _mm256_storeu_si256(
+        meta.as_mut_ptr() as *mut __m256i,
+        _mm256_blendv_epi8(short_meta, hash_meta, hits),
+    );
+
+    meta
+}
+
+/// Same as [`lookup_avx2`] for 8 words.
+#[target_feature(enable = "avx512f")]
+unsafe fn lookup_avx512(encoder: &Encoder, words: &[u64; 8]) -> [u64; 8] {
+    let zero = _mm512_setzero_si512();
+    let low16 = _mm512_set1_epi64(0xFF_FF);
+    let words = _mm512_loadu_si512(words.as_ptr() as *const _);
+
+    let first3 = _mm512_and_si512(words, _mm512_set1_epi64(0xFF_FF_FF));
+    let hashes = _mm512_xor_si512(
+        _mm512_mul_epu32(first3, _mm512_set1_epi64(MULTIPLIER)),
+        _mm512_srli_epi64::<15>(first3),
+    );
+    let slots = _mm512_and_si512(hashes, _mm512_set1_epi64(HASH_TABLE_SIZE as i64 - 1));
+    let values = _mm512_i64gather_epi64::<8>(slots, encoder.hash_values.as_ptr() as *const i64);
+    let hash_meta = _mm512_and_si512(
+        _mm512_cvtepu32_epi64(_mm512_i64gather_epi32::<2>(
+            slots,
+            encoder.hash_meta.as_ptr() as *const i32,
+        )),
+        low16,
+    );
+
+    let bits = _mm512_slli_epi64::<3>(_mm512_srli_epi64::<8>(hash_meta));
+    let masks = _mm512_srlv_epi64(
+        _mm512_set1_epi64(-1),
+        _mm512_sub_epi64(_mm512_set1_epi64(64), bits),
+    );
+    let mismatches = _mm512_and_si512(_mm512_xor_si512(words, values), masks);
+    let hits =
+        _mm512_cmpeq_epi64_mask(mismatches, zero) & _mm512_cmpneq_epi64_mask(hash_meta, zero);
+
+    let short_meta = _mm512_and_si512(
+        _mm512_cvtepu32_epi64(_mm512_i64gather_epi32::<2>(
+            _mm512_and_si512(words, low16),
+            encoder.short_meta.as_ptr() as *const i32,
+        )),
+        low16,
+    );
+
+    let mut meta = [0; 8];
+    _mm512_storeu_si512(
+        meta.as_mut_ptr() as *mut _,
+        _mm512_mask_blend_epi64(hits, short_meta, hash_meta),
+    );
+
+    meta
+}
+
+/// A group of consecutive strings compressed by one vector lane.
+struct Lane<'a> {
+    inputs: &'a [&'a [u8]],
+    next: usize,
+    string: &'a [u8],
+    pos: usize,
+    active: bool,
+    out: Vec<u8>,
+    /// End of each compressed string in `out`.
+    ends: Vec<u64>,
+}
+
+impl<'a> Lane<'a> {
+    fn new(inputs: &'a [&'a [u8]]) -> Self {
+        Self {
+            inputs,
+            next: 0,
+            string: &[],
+            pos: 0,
+            active: true,
+            out: Vec::new(),
+            ends: Vec::with_capacity(inputs.len()),
+        }
+    }
+
+    /// Finishes the current string with the scalar encoder and moves to the
+    /// next one with at least 8 bytes. Clears `active` once the lane has
+    /// compressed all of its strings.
+    fn refill(&mut self, encoder: &Encoder) {
+        loop {
+            if self.next > 0 {
+                encoder.encode(&self.string[self.pos..], &mut self.out);
+                self.ends.push(self.out.len() as u64);
+            }
+
+            let Some(string) = self.inputs.get(self.next) else {
+                self.active = false;
+                return;
+            };
+            self.next += 1;
+            self.string = string;
+            self.pos = 0;
+
+            if string.len() >= 8 {
+                // Every step writes at most 2 bytes per byte it consumes
+                self.out.reserve(2 * string.len());
+                return;
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn encode_lanes<const N: usize>(
+    encoder: &Encoder,
+    inputs: &[&[u8]],
+    out: &mut Vec<u8>,
+    offsets: &mut Vec<u64>,
+    lookup: impl Fn(&[u64; N]) -> [u64; N],
+) {
+    let chunks = split_balanced(inputs, N);
+    let mut lanes: [Lane; N] =
+        std::array::from_fn(|i| Lane::new(chunks.get(i).unwrap_or(&&[][..])));
+    let mut words = [0; N];
+
+    loop {
+        let mut active = false;
+
+        for (lane, word) in lanes.iter_mut().zip(&mut words) {
+            if lane.active && lane.pos + 8 > lane.string.len() {
+                lane.refill(encoder);
+            }
+
+            if lane.active {
+                *word = u64::from_le_bytes(lane.string[lane.pos..lane.pos + 8].try_into().unwrap());
+                active = true;
+            }
+        }
+
+        if !active {
+            break;
+        }
+
+        let meta = lookup(&words);
+
+        for ((lane, meta), word) in lanes.iter_mut().zip(meta).zip(words) {
+            if lane.active {
+                let code = meta as u8;
+                let len = lane.out.len();
+
+                // `refill` reserved room for the escaped byte, which is kept
+                // only after an escape code
+                unsafe {
+                    let p = lane.out.as_mut_ptr().add(len);
+                    p.write(code);
+                    p.add(1).write(word as u8);
+                    lane.out.set_len(len + 1 + (code == 255) as usize);
+                }
+                lane.pos += (meta >> 8) as usize;
+            }
+        }
+    }
+
+    out.clear();
+    out.reserve(lanes.iter().map(|lane| lane.out.len()).sum());
+    offsets.clear();
+    offsets.reserve(inputs.len() + 1);
+    offsets.push(0);
+
+    for lane in lanes {
+        let base = out.len() as u64;
+        out.extend_from_slice(&lane.out);
+        offsets.extend(lane.ends.iter().map(|end| base + end));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::SymbolTable;
+
+    #[test]
+    fn same_output_as_scalar() -> Result<(), Box<dyn std::error::Error>> {
+        for entry in fs::read_dir("tests.nosync/cwida")? {
+            let file = fs::read(entry?.path())?;
+            let lines = file.split(|&b| b == b'\n').collect::<Vec<_>>();
+            let encoder = SymbolTable::build(&lines).into_parts().0;
+
+            let mut expected = (Vec::new(), Vec::new());
+            let mut actual = (Vec::new(), Vec::new());
+
+            encoder.encode_batch_scalar(&lines, &mut expected.0, &mut expected.1);
+
+            if is_x86_feature_detected!("avx2") {
+                unsafe { encode_batch_avx2(&encoder, &lines, &mut actual.0, &mut actual.1) };
+                assert!(actual == expected);
+            }
+            if is_x86_feature_detected!("avx512f") {
+                unsafe { encode_batch_avx512(&encoder, &lines, &mut actual.0, &mut actual.1) };
+                assert!(actual == expected);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,447 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::builder::SymbolTableBuilder;
+use crate::error::invalid_data;
+use crate::table::SymbolTable;
+use crate::varint::{read_varint, write_varint};
+
+const STREAM_MAGIC: &[u8; 4] = b"FSSS";
+const STREAM_VERSION: u8 = 1;
+
+const TRAINING_SIZE: usize = 1 << 20;
+const OUTPUT_BUFFER: usize = 1 << 16;
+
+/// Streaming compressor implementing [`Write`].
+///
+/// The input is cut into records ending with a separator byte (`\n` by
+/// default), which stays part of the record. The first `training_size`
+/// bytes are buffered to train a [`SymbolTable`]; from then on every
+/// complete record is compressed and written as soon as it is received, so
+/// memory stays bounded by the training size and the longest record.
+///
+/// The stream is: a magic number, a version byte, the exported symbol table
+/// preceded by its length, then for each record its compressed length plus
+/// one followed by the compressed bytes, and a final `0`. Lengths are LEB128
+/// varints.
+///
+/// [`FsstWriter::finish`] must be called to compress the buffered data and
+/// write the end of the stream; dropping the writer does so but ignores
+/// errors.
+pub struct FsstWriter<W: Write> {
+    inner: Option<W>,
+    builder: SymbolTableBuilder,
+    separator: u8,
+    training_size: usize,
+    table: Option<SymbolTable>,
+    pending: Vec<u8>,
+    encoded: Vec<u8>,
+    output: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Write> FsstWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            builder: SymbolTableBuilder::new(),
+            separator: b'\n',
+            training_size: TRAINING_SIZE,
+            table: None,
+            pending: Vec::new(),
+            encoded: Vec::new(),
+            output: Vec::with_capacity(OUTPUT_BUFFER),
+            finished: false,
+        }
+    }
+
+    /// Sets the byte that ends a record.
+    pub fn with_separator(mut self, separator: u8) -> Self {
+        self.separator = separator;
+        self
+    }
+
+    /// Sets how many bytes are buffered to train the symbol table.
+    pub fn with_training_size(mut self, training_size: usize) -> Self {
+        self.training_size = training_size;
+        self
+    }
+
+    /// Sets the parameters used to train the symbol table.
+    pub fn with_builder(mut self, builder: SymbolTableBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Compresses the buffered data and writes the end of the stream.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        if self.table.is_none() {
+            self.train()?;
+        }
+
+        if !self.pending.is_empty() {
+            let record = std::mem::take(&mut self.pending);
+            self.write_record(&record)?;
+        }
+
+        write_varint(&mut self.output, 0)?;
+        self.flush_output()?;
+        self.finished = true;
+        self.inner.as_mut().unwrap().flush()
+    }
+
+    /// Finishes the stream and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn train(&mut self) -> io::Result<()> {
+        let records = self
+            .pending
+            .split_inclusive(|&c| c == self.separator)
+            .collect::<Vec<_>>();
+        let table = self.builder.build(&records);
+        let exported = table.export();
+
+        self.output.extend_from_slice(STREAM_MAGIC);
+        self.output.push(STREAM_VERSION);
+        write_varint(&mut self.output, exported.len() as u64)?;
+        self.output.extend_from_slice(&exported);
+        self.table = Some(table);
+
+        let pending = std::mem::take(&mut self.pending);
+        self.write_records(&pending)
+    }
+
+    /// Compresses the complete records of `data` and keeps the rest pending.
+    fn write_records(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut rest = data;
+
+        while let Some(end) = rest.iter().position(|&c| c == self.separator) {
+            self.write_record(&rest[..end + 1])?;
+            rest = &rest[end + 1..];
+        }
+
+        self.pending.extend_from_slice(rest);
+
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &[u8]) -> io::Result<()> {
+        self.encoded.clear();
+        self.table
+            .as_ref()
+            .unwrap()
+            .encode(record, &mut self.encoded);
+
+        write_varint(&mut self.output, self.encoded.len() as u64 + 1)?;
+        self.output.extend_from_slice(&self.encoded);
+
+        if self.output.len() >= OUTPUT_BUFFER {
+            self.flush_output()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush_output(&mut self) -> io::Result<()> {
+        self.inner.as_mut().unwrap().write_all(&self.output)?;
+        self.output.clear();
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for FsstWriter<W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::other("write after finish"));
+        }
+
+        let len = buf.len();
+
+        // Only the training sample is buffered; the rest of a large write is
+        // compressed as soon as the table is trained
+        if self.table.is_none() {
+            let sampled = buf
+                .len()
+                .min(self.training_size.saturating_sub(self.pending.len()));
+            self.pending.extend_from_slice(&buf[..sampled]);
+            buf = &buf[sampled..];
+
+            if self.pending.len() < self.training_size {
+                return Ok(len);
+            }
+
+            self.train()?;
+        }
+
+        if let Some(end) = buf.iter().position(|&c| c == self.separator) {
+            // Complete the pending record first
+            let mut record = std::mem::take(&mut self.pending);
+            record.extend_from_slice(&buf[..end + 1]);
+            self.write_record(&record)?;
+
+            record.clear();
+            self.pending = record;
+            self.write_records(&buf[end + 1..])?;
+        } else {
+            self.pending.extend_from_slice(buf);
+        }
+
+        Ok(len)
+    }
+
+    /// Writes out the records compressed so far. Data that is buffered for
+    /// training or is part of an incomplete record stays buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        self.flush_output()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for FsstWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.try_finish();
+    }
+}
+
+/// Streaming decompressor for the output of [`FsstWriter`], implementing
+/// [`Read`] and [`BufRead`].
+///
+/// Records are decoded one at a time into a reusable buffer. They keep their
+/// separator, so [`BufRead::lines`] works as on the original data.
+pub struct FsstReader<R: Read> {
+    inner: BufReader<R>,
+    table: Box<SymbolTable>,
+    compressed: Vec<u8>,
+    record: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> FsstReader<R> {
+    /// Reads the stream header and the symbol table from `inner`.
+    pub fn new(inner: R) -> io::Result<Self> {
+        let mut inner = BufReader::new(inner);
+
+        let mut header = [0; 5];
+        inner.read_exact(&mut header)?;
+
+        if &header[..4] != STREAM_MAGIC {
+            return Err(invalid_data("not an FSST stream"));
+        }
+
+        if header[4] != STREAM_VERSION {
+            return Err(invalid_data("unsupported FSST stream version"));
+        }
+
+        let len = read_varint(&mut inner)?;
+        let mut table = Vec::new();
+        (&mut inner).take(len).read_to_end(&mut table)?;
+
+        if table.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let table = Box::new(SymbolTable::import(&table).map_err(invalid_data)?);
+
+        Ok(Self {
+            inner,
+            table,
+            compressed: Vec::new(),
+            record: Vec::new(),
+            pos: 0,
+            done: false,
+        })
+    }
+
+    pub fn table(&self) -> &SymbolTable {
+        &self.table
+    }
+
+    /// Returns an iterator over the remaining records. A record that was
+    /// partially read through [`Read`] only yields its unread bytes.
+    pub fn records(self) -> Records<R> {
+        Records { reader: self }
+    }
+
+    /// Decodes the next record, returning `false` at the end of the stream.
+    fn next_record(&mut self) -> io::Result<bool> {
+        if self.done {
+            return Ok(false);
+        }
+
+        let len = read_varint(&mut self.inner)?;
+        if len == 0 {
+            self.done = true;
+            return Ok(false);
+        }
+
+        self.compressed.clear();
+        (&mut self.inner)
+            .take(len - 1)
+            .read_to_end(&mut self.compressed)?;
+
+        if self.compressed.len() as u64 != len - 1 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.table
+            .try_decode(&self.compressed, &mut self.record)
+            .map_err(invalid_data)?;
+        self.pos = 0;
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for FsstReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+
+        Ok(len)
+    }
+}
+
+impl<R: Read> BufRead for FsstReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.record.len() && self.next_record()? {}
+
+        Ok(&self.record[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.record.len());
+    }
+}
+
+/// Iterator over the records of an [`FsstReader`].
+pub struct Records<R: Read> {
+    reader: FsstReader<R>,
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = &mut self.reader;
+
+        if reader.pos == reader.record.len() {
+            match reader.next_record() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+
+        let record = reader.record[reader.pos..].to_vec();
+        reader.pos = reader.record.len();
+
+        Some(Ok(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn writer() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read("tests.nosync/cwida/wiki")?;
+
+        let mut writer = FsstWriter::new(Vec::new()).with_training_size(1 << 16);
+        for chunk in file.chunks(1000) {
+            writer.write_all(chunk)?;
+        }
+        let stream = writer.finish()?;
+        assert!(stream.len() < file.len());
+
+        let mut reader = &stream[..];
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        assert_eq!(&header[..4], STREAM_MAGIC);
+
+        let mut table = vec![0; read_varint(&mut reader)? as usize];
+        reader.read_exact(&mut table)?;
+        let table = SymbolTable::import(&table)?;
+
+        let mut decoded = Vec::new();
+        let mut record = Vec::new();
+        let mut buffer = Vec::new();
+        loop {
+            let len = read_varint(&mut reader)? as usize;
+            if len == 0 {
+                break;
+            }
+
+            buffer.resize(len - 1, 0);
+            reader.read_exact(&mut buffer)?;
+            table.try_decode(&buffer, &mut record)?;
+            assert!(record.len() <= 1 || !record[..record.len() - 1].contains(&b'\n'));
+            decoded.extend_from_slice(&record);
+        }
+
+        assert!(reader.is_empty());
+        assert_eq!(decoded, file);
+
+        Ok(())
+    }
+
+    #[test]
+    fn reader() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read("tests.nosync/cwida/location")?;
+
+        let mut writer = FsstWriter::new(Vec::new()).with_training_size(1 << 16);
+        writer.write_all(&file)?;
+
+        let stream = writer.finish()?;
+
+        let mut decoded = Vec::new();
+        FsstReader::new(&stream[..])?.read_to_end(&mut decoded)?;
+        assert_eq!(decoded, file);
+
+        // A single large write is trained on, and buffered up to, the
+        // training size only
+        let sample = file[..1 << 16]
+            .split_inclusive(|&c| c == b'\n')
+            .collect::<Vec<_>>();
+        assert_eq!(
+            FsstReader::new(&stream[..])?.table().export(),
+            SymbolTable::build(&sample).export()
+        );
+
+        let text = String::from_utf8(file)?;
+        let lines = FsstReader::new(&stream[..])?.lines();
+        assert!(lines.map(Result::unwrap).eq(text.lines().map(String::from)));
+
+        let records = FsstReader::new(&stream[..])?.records();
+        assert!(records
+            .map(Result::unwrap)
+            .eq(text.split_inclusive('\n').map(|s| s.as_bytes().to_vec())));
+
+        let truncated = FsstReader::new(&stream[..stream.len() - 1])?;
+        assert!(truncated.records().any(|record| record.is_err()));
+
+        Ok(())
+    }
+}
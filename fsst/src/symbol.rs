@@ -1,29 +1,41 @@
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+/// A symbol of up to 8 bytes, packed little-endian into a `u64`.
+///
+/// `len` is expressed in **bits** (a multiple of 8), so a 3-byte symbol has
+/// `len == 24`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
 pub struct Symbol {
+    /// Symbol bytes, first byte in the low 8 bits; unused bytes are 0.
     pub value: u64,
+    /// Symbol length in bits.
     pub len: usize,
 }
 
 impl Symbol {
+    /// Returns the empty symbol.
     pub fn new() -> Self {
         Self { value: 0, len: 0 }
     }
 
+    /// Returns the symbol of `len` bits packed in `value`.
     pub fn with(value: u64, len: usize) -> Self {
         Self { value, len }
     }
 
+    /// Appends the byte `char`. The symbol must be shorter than 8 bytes.
     #[inline]
     pub fn add_char(&mut self, char: u8) {
         self.value |= (char as u64) << self.len;
         self.len += 8;
     }
 
+    /// Returns whether `other` is a prefix of this symbol.
     #[inline]
     pub fn starts_with(&self, other: &Self) -> bool {
-        ((self.value ^ other.value) & (u64::MAX >> (64 - other.len))) == 0
+        self.len >= other.len && ((self.value ^ other.value) & (u64::MAX >> (64 - other.len))) == 0
     }
 
+    /// Returns this symbol followed by `other`. Panics if the result is
+    /// longer than 8 bytes.
     #[inline]
     pub fn extend(&self, other: &Self) -> Self {
         assert!(self.len + other.len <= 64);
@@ -31,16 +43,19 @@ impl Symbol {
         Self::with(self.value | (other.value << self.len), self.len + other.len)
     }
 
+    /// Returns the first 3 bytes.
     #[inline]
     pub fn first3byte(&self) -> u64 {
         self.value & 0xFF_FF_FF
     }
 
+    /// Returns the first 2 bytes.
     #[inline]
     pub fn first2byte(&self) -> u64 {
         self.value & 0xFF_FF
     }
 
+    /// Returns the first byte. Panics if the symbol is empty.
     #[inline]
     pub fn first1byte(&self) -> u64 {
         assert!(self.len != 0);
@@ -1,388 +1,597 @@
-use std::cmp::min;
-use std::collections::BinaryHeap;
-use std::slice;
-
-use crate::counters::{Counters, TABLE_LENGTH};
-use crate::heap::HeapPair;
-use crate::lossy_pht::{hash, LossyPHS, TableEntry};
+use crate::builder::SymbolTableBuilder;
+use crate::counters::TABLE_LENGTH;
+use crate::decoder::Decoder;
+use crate::encoder::Encoder;
+use crate::error::{DecodeError, ImportError};
 use crate::symbol::Symbol;
 
-const GENERATIONS: [usize; 5] = [8, 38, 68, 98, 128];
-const SYMBOL_LENGTH: usize = 8;
-const FSST_SAMPLETARGET: usize = 1 << 14;
-const FSST_SAMPLEMAX: usize = 1 << 15;
-const FSST_SAMPLELINE: usize = 512;
-
+pub(crate) const SYMBOL_LENGTH: usize = 8;
+const EXPORT_VERSION: u8 = 1;
+const EXPORT_VERSION_FLAGS: u8 = 2;
+const FLAG_ZERO_TERMINATED: u8 = 1;
+pub(crate) const CWIDA_VERSION: u64 = 20190218;
+const CWIDA_ENDIAN_MARKER: u64 = 1;
+pub(crate) const CWIDA_HEADER: usize = 17;
+
+/// A trained FSST symbol table: up to 255 symbols of 1 to 8 bytes, each
+/// mapped to a 1-byte code. Code 255 is the escape code, followed by a
+/// literal byte.
+///
+/// The table is an [`Encoder`] and a [`Decoder`] side by side. Both can be
+/// cloned and shared between threads on their own, so a reader can keep only
+/// the few kilobytes of the decoder; see [`SymbolTable::into_parts`].
+#[derive(Clone)]
 pub struct SymbolTable {
-    n_symbols: usize,
-    symbols_1_byte: [TableEntry; 256],
-    symbols_2_byte: Vec<TableEntry>,
-    phs: LossyPHS,
-    symbols: [Symbol; 2 * TABLE_LENGTH],
-    counters: Counters,
+    encoder: Encoder,
+    decoder: Decoder,
 }
 
 impl SymbolTable {
-    fn new() -> Self {
-        let mut symbols = [Symbol::new(); 2 * TABLE_LENGTH];
-
-        for (code, item) in symbols.iter_mut().enumerate().take(TABLE_LENGTH) {
-            item.add_char(code as u8);
-        }
+    /// Builds the table in which code `i` stands for `symbols[i]`. In
+    /// zero-terminated mode `symbols[0]` must be the byte 0.
+    pub(crate) fn from_symbols(symbols: &[Symbol], zero_terminated: bool) -> Self {
+        debug_assert!(!zero_terminated || symbols.first() == Some(&Symbol::with(0, 8)));
 
         Self {
-            n_symbols: 0,
-            symbols,
-            phs: LossyPHS::new(),
-            symbols_1_byte: [TableEntry {
-                val: 0,
-                used: false,
-            }; 256],
-            symbols_2_byte: vec![
-                TableEntry {
-                    val: 0,
-                    used: false
-                };
-                65_536
-            ],
-            counters: Counters::new(),
+            encoder: Encoder::new(symbols, zero_terminated),
+            decoder: Decoder::new(symbols, zero_terminated),
         }
     }
 
-    fn insert(&mut self, s: Symbol) {
-        let idx;
-
-        if s.len == 16 {
-            idx = s.first2byte() as usize;
-            self.symbols_2_byte[idx].val = TABLE_LENGTH + self.n_symbols;
-            self.symbols_2_byte[idx].used = true;
-        } else if s.len == 8 {
-            idx = s.first1byte() as usize;
-            self.symbols_1_byte[idx].val = TABLE_LENGTH + self.n_symbols;
-            self.symbols_1_byte[idx].used = true;
-        } else if !self.phs.add(s.first3byte(), TABLE_LENGTH + self.n_symbols) {
-            return;
-        }
-
-        self.symbols[TABLE_LENGTH + self.n_symbols] = s;
-        self.n_symbols += 1;
+    /// Trains a symbol table on a sample of `text` with the default
+    /// parameters of [`SymbolTableBuilder`].
+    pub fn build(text: &[&[u8]]) -> Self {
+        SymbolTableBuilder::new().build(text)
     }
 
-    fn compress_count(&mut self, text: &[u8]) {
-        let mut p_start = text.as_ptr();
-        let mut p_end;
-        let mut prev;
-        let mut code = 0;
-        let mut next_char;
-        let mut symbol;
-
-        if text.len() >= 8 {
-            unsafe {
-                p_end = text.as_ptr().add(text.len() - 8);
-            }
+    /// Returns the encoder of this table.
+    pub fn encoder(&self) -> &Encoder {
+        &self.encoder
+    }
 
-            while p_start <= p_end {
-                prev = code;
+    /// Returns the decoder of this table.
+    pub fn decoder(&self) -> &Decoder {
+        &self.decoder
+    }
 
-                unsafe {
-                    symbol = Symbol::with((p_start as *const u64).read_unaligned(), 64);
-                }
+    /// Whether code 0 is reserved for the byte 0, which then terminates every
+    /// compressed string. See [`SymbolTableBuilder::zero_terminated`].
+    pub fn is_zero_terminated(&self) -> bool {
+        self.encoder.zero_terminated
+    }
 
-                code = self.find_longest_symbol(&symbol);
+    /// Splits the table into its encoder and decoder.
+    pub fn into_parts(self) -> (Encoder, Decoder) {
+        (self.encoder, self.decoder)
+    }
 
-                self.counters.incr_c1(code);
-                self.counters.incr_c2(prev, code);
+    /// Serializes the table as: a version byte, the number of symbols, the
+    /// length in bytes of each symbol, the symbol bytes and a CRC-32 of all
+    /// the preceding bytes (little-endian).
+    ///
+    /// Zero-terminated tables are written as version 2, which has a flags
+    /// byte after the version byte.
+    pub fn export(&self) -> Vec<u8> {
+        let symbols = self.decoder.symbols();
+        let mut buffer = Vec::with_capacity(3 + 9 * symbols.len() + 4);
+
+        if self.is_zero_terminated() {
+            buffer.push(EXPORT_VERSION_FLAGS);
+            buffer.push(FLAG_ZERO_TERMINATED);
+        } else {
+            buffer.push(EXPORT_VERSION);
+        }
 
-                if code >= TABLE_LENGTH {
-                    next_char = symbol.first1byte() as usize;
-                    self.counters.incr_c1(next_char);
-                    self.counters.incr_c2(prev, next_char);
-                }
+        buffer.push(symbols.len() as u8);
+        buffer.extend(symbols.iter().map(|s| (s.len / 8) as u8));
 
-                unsafe {
-                    p_start = p_start.add(self.symbols[code].len / 8);
-                }
-            }
+        for s in &symbols {
+            buffer.extend_from_slice(&s.value.to_le_bytes()[..s.len / 8]);
         }
 
-        unsafe {
-            p_end = text.as_ptr().add(text.len());
+        let checksum = crc32(&buffer);
+        buffer.extend_from_slice(&checksum.to_le_bytes());
+
+        buffer
+    }
+
+    /// Rebuilds a table serialized with [`SymbolTable::export`].
+    pub fn import(buffer: &[u8]) -> Result<Self, ImportError> {
+        let (flags, header) = match buffer.first() {
+            None => return Err(ImportError::UnexpectedEnd),
+            Some(&EXPORT_VERSION) => (0, 1),
+            Some(&EXPORT_VERSION_FLAGS) => (*buffer.get(1).ok_or(ImportError::UnexpectedEnd)?, 2),
+            Some(&version) => return Err(ImportError::UnsupportedVersion(version as u32)),
+        };
+
+        if buffer.len() < header + 1 + 4 {
+            return Err(ImportError::UnexpectedEnd);
         }
 
-        // Remaining bytes (less than 8)
+        let n_symbols = buffer[header] as usize;
+        let lengths = buffer
+            .get(header + 1..header + 1 + n_symbols)
+            .ok_or(ImportError::UnexpectedEnd)?;
+        let total: usize = lengths.iter().map(|&l| l as usize).sum();
+        let end = header + 1 + n_symbols + total;
 
-        while p_start < p_end {
-            prev = code;
+        if buffer.len() < end + 4 {
+            return Err(ImportError::UnexpectedEnd);
+        }
 
-            unsafe {
-                let offset = 8 * p_end.offset_from(p_start);
-                symbol = Symbol::with(
-                    (p_start as *const u64).read_unaligned(),
-                    min(64, offset as usize),
-                );
-            }
+        let checksum = u32::from_le_bytes(buffer[end..end + 4].try_into().unwrap());
+        if checksum != crc32(&buffer[..end]) {
+            return Err(ImportError::ChecksumMismatch);
+        }
 
-            code = self.find_longest_symbol(&symbol);
+        let mut symbols = Vec::with_capacity(n_symbols);
+        let mut offset = header + 1 + n_symbols;
 
-            self.counters.incr_c1(code);
-            self.counters.incr_c2(prev, code);
+        for (code, &len) in lengths.iter().enumerate() {
+            let len = len as usize;
 
-            if code >= TABLE_LENGTH {
-                next_char = symbol.first1byte() as usize;
-                self.counters.incr_c1(next_char);
-                self.counters.incr_c2(prev, next_char);
+            if len == 0 || len > SYMBOL_LENGTH || code >= TABLE_LENGTH - 1 {
+                return Err(ImportError::InvalidSymbol(code));
             }
 
-            unsafe {
-                p_start = p_start.add(self.symbols[code].len / 8);
-            }
+            symbols.push(symbol_from_bytes(&buffer[offset..offset + len]));
+            offset += len;
         }
-    }
 
-    fn make_table(&mut self, sample_frac: usize) {
-        let mut cands = BinaryHeap::with_capacity(65_536);
-        let mut gain;
-        let mut s1;
-        let mut s2;
-        let mut count;
-        let mut length1;
-
-        for code1 in 0..(TABLE_LENGTH + self.n_symbols) {
-            count = self.counters.get_from_c1(code1);
+        let zero_terminated = flags & FLAG_ZERO_TERMINATED != 0;
+        if zero_terminated && symbols.first() != Some(&Symbol::with(0, 8)) {
+            return Err(ImportError::InvalidSymbol(0));
+        }
 
-            if count < (5 * sample_frac / 128) {
-                continue;
-            }
+        Ok(Self::from_symbols(&symbols, zero_terminated))
+    }
 
-            s1 = self.symbols[code1];
+    /// Serializes the table in the byte layout of the reference
+    /// `fsst_export`: a little-endian version word, the zero-terminated flag,
+    /// the histogram of symbol lengths and the symbol bytes.
+    ///
+    /// In zero-terminated mode the byte 0 of code 0 counts in the histogram
+    /// but is not stored, and `fsst_import` restores it.
+    pub fn export_cwida(&self) -> Vec<u8> {
+        let symbols = self.decoder.symbols();
+        let zero_terminated = self.is_zero_terminated() as usize;
+        let mut len_histo = [0u8; 8];
+
+        for s in &symbols {
+            len_histo[s.len / 8 - 1] += 1;
+        }
 
-            length1 = s1.len / 8;
+        // 2-byte codes start after the terminator
+        let suffix_lim = zero_terminated
+            + (0..symbols.len())
+                .filter(|&code| symbols[code].len == 16 && !has_extension(&symbols, code))
+                .count();
+
+        // The terminator byte is only used by the reference encoder and is
+        // ignored by `fsst_import`.
+        let version = (CWIDA_VERSION << 32)
+            | ((suffix_lim as u64) << 24)
+            | ((symbols.len() as u64) << 8)
+            | CWIDA_ENDIAN_MARKER;
+
+        let mut buffer = Vec::with_capacity(CWIDA_HEADER + 8 * symbols.len());
+        buffer.extend_from_slice(&version.to_le_bytes());
+        buffer.push(zero_terminated as u8);
+        buffer.extend_from_slice(&len_histo);
+
+        for s in &symbols[zero_terminated..] {
+            buffer.extend_from_slice(&s.value.to_le_bytes()[..s.len / 8]);
+        }
 
-            gain = length1 * count;
+        buffer
+    }
 
-            if code1 < 256 {
-                gain *= 8;
-            }
+    /// Rebuilds a table serialized by the reference `fsst_export` or by
+    /// [`SymbolTable::export_cwida`].
+    pub fn import_cwida(buffer: &[u8]) -> Result<Self, ImportError> {
+        if buffer.len() < CWIDA_HEADER {
+            return Err(ImportError::UnexpectedEnd);
+        }
 
-            cands.push(HeapPair(gain, s1));
+        let version = u64::from_le_bytes(buffer[..8].try_into().unwrap());
+        if version >> 32 != CWIDA_VERSION {
+            return Err(ImportError::UnsupportedVersion((version >> 32) as u32));
+        }
 
-            if sample_frac >= 128 || length1 == SYMBOL_LENGTH {
-                continue;
-            }
+        let zero_terminated = buffer[8] & 1 != 0;
+        let mut len_histo: [u8; 8] = buffer[9..CWIDA_HEADER].try_into().unwrap();
+        let mut symbols = Vec::new();
+        let mut offset = CWIDA_HEADER;
 
-            for code2 in 0..(TABLE_LENGTH + self.n_symbols) {
-                s2 = self.symbols[code2];
+        if zero_terminated {
+            len_histo[0] = len_histo[0]
+                .checked_sub(1)
+                .ok_or(ImportError::InvalidSymbol(0))?;
+            symbols.push(Symbol::with(0, 8));
+        }
 
-                if length1 + s2.len / 8 > SYMBOL_LENGTH {
-                    continue;
+        // Codes are assigned to 2- to 8-byte symbols first, 1-byte symbols last
+        for len in (2..=SYMBOL_LENGTH).chain(1..=1) {
+            for _ in 0..len_histo[len - 1] {
+                if symbols.len() >= TABLE_LENGTH - 1 {
+                    return Err(ImportError::InvalidSymbol(symbols.len()));
                 }
 
-                let new: Symbol = s1.extend(&s2);
-                gain = new.len / 8 * self.counters.get_from_c2(code1, code2);
-                cands.push(HeapPair(gain, new));
+                let bytes = buffer
+                    .get(offset..offset + len)
+                    .ok_or(ImportError::UnexpectedEnd)?;
+
+                symbols.push(symbol_from_bytes(bytes));
+                offset += len;
             }
         }
 
-        self.clear();
+        Ok(Self::from_symbols(&symbols, zero_terminated))
+    }
 
-        while !cands.is_empty() && self.n_symbols < TABLE_LENGTH - 1 {
-            let HeapPair(_, sym) = cands.pop().unwrap();
-            self.insert(sym);
-        }
+    /// Compresses `string`, appending the codes to `buffer`.
+    pub fn encode(&self, string: &[u8], buffer: &mut Vec<u8>) {
+        self.encoder.encode(string, buffer)
     }
 
-    pub fn build(text: &[&[u8]]) -> Self {
-        let mut st = SymbolTable::new();
+    /// Compresses a predicate constant once, so it can be compared with
+    /// strings compressed by this table without decoding them.
+    ///
+    /// Encoding is deterministic, so two strings are equal if and only if
+    /// their codes from the same table are.
+    pub fn encode_constant(&self, needle: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(2 * needle.len() + 1);
+        self.encode(needle, &mut buffer);
 
-        let mut sample_memory = Vec::with_capacity(FSST_SAMPLEMAX);
-        let sample = make_sample(&mut sample_memory, text);
+        buffer
+    }
 
-        for &x in GENERATIONS.iter() {
-            for (_, line) in sample.iter().enumerate() {
-                /*if x < 128 && ((hash(i as u64) & 127) as usize) > x {
-                    continue;
-                }*/
+    /// See [`Encoder::encode_batch`].
+    pub fn encode_batch(&self, inputs: &[&[u8]], out: &mut Vec<u8>, offsets: &mut Vec<u64>) {
+        self.encoder.encode_batch(inputs, out, offsets)
+    }
 
-                st.compress_count(line);
-            }
+    /// See [`Encoder::encode_batch_parallel`].
+    pub fn encode_batch_parallel(
+        &self,
+        inputs: &[&[u8]],
+        out: &mut Vec<u8>,
+        offsets: &mut Vec<u64>,
+        threads: usize,
+    ) {
+        self.encoder
+            .encode_batch_parallel(inputs, out, offsets, threads)
+    }
 
-            st.make_table(x);
-        }
+    /// See [`Encoder::encode_batch_into`].
+    pub fn encode_batch_into(
+        &self,
+        inputs: &[&[u8]],
+        out: &mut [u8],
+        offsets: &mut [u64],
+    ) -> usize {
+        self.encoder.encode_batch_into(inputs, out, offsets)
+    }
 
-        st
+    /// Decompresses `string` into `buffer`, overwriting its contents.
+    ///
+    /// Panics if `string` ends with an escape code. Use
+    /// [`SymbolTable::try_decode`] for untrusted input.
+    pub fn decode(&self, string: &[u8], buffer: &mut Vec<u8>) {
+        self.decoder.decode(string, buffer)
     }
 
-    fn find_longest_symbol(&self, text: &Symbol) -> usize {
-        let mut s = self.phs.get(text.first3byte());
+    /// See [`Decoder::try_decode`].
+    pub fn try_decode(&self, string: &[u8], buffer: &mut Vec<u8>) -> Result<(), DecodeError> {
+        self.decoder.try_decode(string, buffer)
+    }
 
-        if s.used && text.starts_with(&self.symbols[s.val]) {
-            return s.val;
-        }
+    /// See [`Decoder::decode_into`].
+    pub fn decode_into(&self, string: &[u8], out: &mut [u8]) -> Result<usize, DecodeError> {
+        self.decoder.decode_into(string, out)
+    }
 
-        s = self.symbols_2_byte[text.first2byte() as usize];
-        if s.used {
-            return s.val;
-        }
+    /// Returns the decompressed length of `string` without decoding it.
+    pub fn decoded_len(&self, string: &[u8]) -> Result<usize, DecodeError> {
+        self.decoder.decoded_len(string)
+    }
 
-        s = self.symbols_1_byte[text.first1byte() as usize];
-        if s.used {
-            return s.val;
-        }
+    /// See [`Decoder::decode_unchecked`].
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must have a capacity of at least `8 * string.len()` bytes.
+    pub unsafe fn decode_unchecked(&self, string: &[u8], buffer: &mut Vec<u8>) {
+        unsafe { self.decoder.decode_unchecked(string, buffer) }
+    }
 
-        text.first1byte() as usize
+    /// See [`Decoder::decode_batch`].
+    pub fn decode_batch(
+        &self,
+        data: &[u8],
+        offsets: &[u64],
+        out: &mut Vec<u8>,
+        out_offsets: &mut Vec<u64>,
+    ) {
+        self.decoder.decode_batch(data, offsets, out, out_offsets)
     }
 
-    pub fn decode(&self, string: &[u8], buffer: &mut Vec<u8>) {
-        let mut p_start: *mut u8 = buffer.as_mut_ptr();
-        let mut i = 0;
-
-        while i < string.len() {
-            if string[i] != 255 {
-                unsafe {
-                    (p_start as *mut u64)
-                        .write_unaligned(self.symbols[TABLE_LENGTH + string[i] as usize].value);
-                    p_start = p_start.add(self.symbols[TABLE_LENGTH + string[i] as usize].len / 8);
-                }
+    /// See [`Decoder::decode_batch_selected`].
+    pub fn decode_batch_selected(
+        &self,
+        data: &[u8],
+        offsets: &[u64],
+        selection: &[usize],
+        out: &mut Vec<u8>,
+        out_offsets: &mut Vec<u64>,
+    ) {
+        self.decoder
+            .decode_batch_selected(data, offsets, selection, out, out_offsets)
+    }
+}
 
-                i += 1;
-            } else {
-                unsafe {
-                    *p_start = string[i + 1];
-                    p_start = p_start.add(1);
-                }
+/// Whether another symbol of at least 2 bytes starts with the same 2 bytes
+/// as the 2-byte symbol `symbols[code]`.
+pub(crate) fn has_extension(symbols: &[Symbol], code: usize) -> bool {
+    let first2 = symbols[code].first2byte();
 
-                i += 2;
-            }
-        }
+    symbols
+        .iter()
+        .enumerate()
+        .any(|(k, s)| k != code && s.len > 8 && s.first2byte() == first2)
+}
 
-        unsafe {
-            buffer.set_len(p_start.offset_from(buffer.as_ptr()) as usize);
-        }
-    }
+fn symbol_from_bytes(bytes: &[u8]) -> Symbol {
+    let mut s = Symbol::new();
 
-    pub fn encode(&self, string: &[u8], buffer: &mut Vec<u8>) {
-        let mut p_start = string.as_ptr();
-        let mut p_end;
-        let mut symbol;
-        let mut code;
-
-        if string.len() >= 8 {
-            unsafe {
-                p_end = string.as_ptr().add(string.len() - 8);
-            }
+    for &c in bytes {
+        s.add_char(c);
+    }
 
-            while p_start <= p_end {
-                unsafe {
-                    symbol = Symbol::with((p_start as *const u64).read_unaligned(), 64);
-                }
+    s
+}
 
-                code = self.find_longest_symbol(&symbol);
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
 
-                if code >= TABLE_LENGTH {
-                    buffer.push((code - TABLE_LENGTH) as u8);
-                } else {
-                    buffer.push(255);
-                    buffer.push(symbol.first1byte() as u8);
-                }
+    for &byte in data {
+        crc ^= byte as u32;
 
-                unsafe {
-                    p_start = p_start.add(self.symbols[code].len / 8);
-                }
-            }
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & 0u32.wrapping_sub(crc & 1));
         }
+    }
+
+    !crc
+}
 
-        unsafe {
-            p_end = string.as_ptr().add(string.len());
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn export_import() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/urls2")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+
+        let st = SymbolTable::build(&lines);
+        let exported = st.export();
+        let imported = SymbolTable::import(&exported)?;
+
+        assert_eq!(imported.export(), exported);
+
+        let mut buffer = Vec::new();
+        let mut buffer_imp = Vec::new();
+        for line in lines {
+            buffer.clear();
+            buffer_imp.clear();
+            st.encode(line, &mut buffer);
+            imported.encode(line, &mut buffer_imp);
+            assert_eq!(buffer, buffer_imp);
         }
 
-        while p_start < p_end {
-            unsafe {
-                let offset = 8 * p_end.offset_from(p_start);
-                symbol = Symbol::with(
-                    (p_start as *const u64).read_unaligned(),
-                    min(64, offset as usize),
-                );
-            }
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let mut corrupted = exported.clone();
+        let last = corrupted.len() - 5;
+        corrupted[last] ^= 1;
+        assert_eq!(
+            SymbolTable::import(&corrupted).err(),
+            Some(ImportError::ChecksumMismatch)
+        );
+        assert_eq!(
+            SymbolTable::import(&exported[..exported.len() - 1]).err(),
+            Some(ImportError::UnexpectedEnd)
+        );
+
+        Ok(())
+    }
 
-            code = self.find_longest_symbol(&symbol);
+    #[test]
+    fn cwida_format() -> Result<(), Box<dyn std::error::Error>> {
+        // Table with the symbols "ht", "tp", "://", "www.", ".com/" and "e",
+        // written by hand following the `fsst_export` layout. It was not
+        // produced by the C++ library, so it only checks the layout.
+        let fixture = fs::read("tests.nosync/tables/urls.fsst")?;
+        let st = SymbolTable::import_cwida(&fixture)?;
+        assert_eq!(st.export_cwida(), fixture);
 
-            if code >= TABLE_LENGTH {
-                buffer.push((code - TABLE_LENGTH) as u8);
-            } else {
-                buffer.push(255);
-                buffer.push(symbol.first1byte() as u8);
-            }
+        let text = b"http://www.example.com/";
+        let compressed = [
+            0, 1, 2, 3, 5, 255, b'x', 255, b'a', 255, b'm', 255, b'p', 255, b'l', 5, 4,
+        ];
 
-            unsafe {
-                p_start = p_start.add(self.symbols[code].len / 8);
-            }
-        }
-    }
+        let mut buffer = Vec::new();
+        st.encode(text, &mut buffer);
+        assert_eq!(buffer, compressed);
 
-    fn clear(&mut self) {
-        for code in 0..(TABLE_LENGTH + self.n_symbols) {
-            let symbol = self.symbols[code];
-
-            if symbol.len == 8 {
-                self.symbols_1_byte[symbol.first1byte() as usize] = TableEntry {
-                    val: 0,
-                    used: false,
-                };
-            } else if symbol.len == 16 {
-                self.symbols_2_byte[symbol.first2byte() as usize] = TableEntry {
-                    val: 0,
-                    used: false,
-                };
-            } else {
-                self.phs.remove(symbol.first3byte());
-            }
-        }
+        let mut decoded = Vec::new();
+        st.decode(&compressed, &mut decoded);
+        assert_eq!(decoded, text);
 
-        self.counters.clear();
+        let file = fs::read_to_string("tests.nosync/cwida/urls2")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
 
-        self.n_symbols = 0;
-    }
-}
+        let st = SymbolTable::build(&lines);
+        let imported = SymbolTable::import_cwida(&st.export_cwida())?;
+        assert_eq!(imported.export(), st.export());
 
-fn make_sample<'a>(sample_buf: &'a mut Vec<u8>, text: &'a [&'a [u8]]) -> Vec<&'a [u8]> {
-    let mut sample: Vec<&[u8]> = Vec::new();
+        Ok(())
+    }
 
-    let total_size: usize = text.iter().map(|s| s.len()).sum();
-    if total_size < FSST_SAMPLETARGET {
-        return text.to_owned();
+    #[test]
+    fn try_decode() -> Result<(), Box<dyn std::error::Error>> {
+        let st = SymbolTable::import_cwida(&fs::read("tests.nosync/tables/urls.fsst")?)?;
+        let mut buffer = Vec::new();
+
+        st.try_decode(&[0, 1, 2, 255, b'x'], &mut buffer)?;
+        assert_eq!(buffer, b"http://x");
+
+        assert_eq!(
+            st.try_decode(&[0, 1, 255], &mut buffer),
+            Err(DecodeError::TruncatedEscape { position: 2 })
+        );
+        assert!(buffer.is_empty());
+
+        assert_eq!(
+            st.try_decode(&[0, 6], &mut buffer),
+            Err(DecodeError::UnknownCode {
+                code: 6,
+                position: 1
+            })
+        );
+
+        Ok(())
     }
 
-    let mut sample_rnd = hash(4637947);
-    let sample_lim = FSST_SAMPLETARGET;
-    let mut sample_buf_offset: usize = 0;
+    #[test]
+    fn decode_into() -> Result<(), Box<dyn std::error::Error>> {
+        let st = SymbolTable::import_cwida(&fs::read("tests.nosync/tables/urls.fsst")?)?;
+        let compressed = [0, 1, 2, 3, 5, 255, b'x', 4];
+        let text = b"http://www.ex.com/";
 
-    while sample_buf_offset < sample_lim {
-        sample_rnd = hash(sample_rnd);
-        let line_nr = (sample_rnd as usize) % text.len();
+        assert_eq!(st.decoded_len(&compressed)?, text.len());
 
-        let Some(line) = (line_nr..text.len())
-            .chain(0..line_nr)
-            .map(|line_nr| text[line_nr])
-            .find(|line| !line.is_empty())
-        else {
-            return sample;
-        };
+        let mut out = [0; 64];
+        assert_eq!(st.decode_into(&compressed, &mut out)?, text.len());
+        assert_eq!(&out[..text.len()], text);
 
-        let chunks = 1 + ((line.len() - 1) / FSST_SAMPLELINE);
-        sample_rnd = hash(sample_rnd);
-        let chunk = FSST_SAMPLELINE * ((sample_rnd as usize) % chunks);
+        let mut out = [0; 18];
+        assert_eq!(st.decode_into(&compressed, &mut out)?, text.len());
+        assert_eq!(&out, text);
 
-        let len = FSST_SAMPLELINE.min(line.len() - chunk);
+        let mut out = [0; 12];
+        assert_eq!(
+            st.decode_into(&compressed, &mut out),
+            Err(DecodeError::OutputOverflow { needed: 18 })
+        );
 
-        sample_buf.extend_from_slice(&line[chunk..chunk + len]);
+        Ok(())
+    }
 
-        let slice =
-            unsafe { slice::from_raw_parts(sample_buf.as_ptr().add(sample_buf_offset), len) };
+    #[test]
+    fn into_parts() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/email")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+
+        let st = SymbolTable::build(&lines);
+        let exported = st.export();
+        let (encoder, decoder) = st.into_parts();
+        let (encoder, decoder) = (Arc::new(encoder), Arc::new(decoder));
+        let line = lines[0];
+
+        let compressed = thread::scope(|scope| {
+            let encoder = Arc::clone(&encoder);
+            scope
+                .spawn(move || {
+                    let mut compressed = Vec::new();
+                    encoder.encode(line, &mut compressed);
+                    compressed
+                })
+                .join()
+                .unwrap()
+        });
+
+        let mut decoded = Vec::new();
+        decoder.decode(&compressed, &mut decoded);
+        assert_eq!(decoded, line);
+
+        let st = SymbolTable::import(&exported)?;
+        let mut buffer = Vec::new();
+        st.encode(line, &mut buffer);
+        assert_eq!(buffer, compressed);
+
+        Ok(())
+    }
 
-        sample.push(slice);
+    #[test]
+    fn zero_terminated() -> Result<(), Box<dyn std::error::Error>> {
+        let file = fs::read_to_string("tests.nosync/cwida/urls2")?;
+        let lines = file.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
+
+        let st = SymbolTableBuilder::new()
+            .zero_terminated(true)
+            .build(&lines);
+        assert!(st.is_zero_terminated());
+
+        let mut out = Vec::new();
+        let mut offsets = Vec::new();
+        st.encode_batch(&lines, &mut out, &mut offsets);
+
+        let mut decoded = Vec::new();
+        for (i, line) in lines.iter().enumerate() {
+            let compressed = &out[offsets[i] as usize..offsets[i + 1] as usize];
+            assert_eq!(
+                compressed.iter().position(|&b| b == 0),
+                Some(compressed.len() - 1)
+            );
+
+            st.decode(compressed, &mut decoded);
+            assert_eq!(&decoded, line);
+            assert_eq!(
+                st.decoded_len(&compressed[..compressed.len() - 1])?,
+                line.len()
+            );
+        }
 
-        sample_buf_offset += len;
+        // An embedded 0 is code 0 too, but only the terminator is dropped
+        let mut buffer = Vec::new();
+        st.encode(b"ab\0cd", &mut buffer);
+        st.try_decode(&buffer, &mut decoded)?;
+        assert_eq!(decoded, b"ab\0cd");
+        assert_eq!(st.decoded_len(&buffer)?, 5);
+
+        // A trailing 0 byte must still be counted when the output is too small
+        buffer.clear();
+        st.encode(b"ab\0", &mut buffer);
+        let mut out = vec![0; 1];
+        let needed = match st.decode_into(&buffer, &mut out) {
+            Err(DecodeError::OutputOverflow { needed }) => needed,
+            result => panic!("expected an overflow, got {:?}", result),
+        };
+        assert_eq!(needed, 3);
+        out.resize(needed, 0);
+        assert_eq!(st.decode_into(&buffer, &mut out)?, 3);
+        assert_eq!(out, b"ab\0");
+
+        let imported = SymbolTable::import(&st.export())?;
+        assert!(imported.is_zero_terminated());
+        assert_eq!(imported.export(), st.export());
+
+        // Byte 0 is counted in the histogram but not stored
+        let native = st.export();
+        let symbol_bytes = native.len() - 3 - native[2] as usize - 4;
+        let exported = st.export_cwida();
+        assert_eq!(exported[8], 1);
+        assert_eq!(exported.len(), CWIDA_HEADER + symbol_bytes - 1);
+        let imported = SymbolTable::import_cwida(&exported)?;
+        assert!(imported.is_zero_terminated());
+        assert_eq!(imported.export(), st.export());
+
+        Ok(())
     }
-
-    sample
 }
@@ -0,0 +1,297 @@
+use std::collections::BinaryHeap;
+use std::ptr;
+
+use crate::counters::{Counters, TABLE_LENGTH};
+use crate::heap::HeapPair;
+use crate::lossy_pht::{LossyPHS, TableEntry};
+use crate::symbol::Symbol;
+use crate::table::{has_extension, SymbolTable, SYMBOL_LENGTH};
+
+/// The state of a symbol table during training: the symbols of the current
+/// generation, with lookup structures to count them on the sample, and the
+/// single-byte pseudo-symbols of escaped bytes at codes 0 to 255.
+///
+/// [`Trainer::finish`] turns it into a compact [`SymbolTable`].
+pub(crate) struct Trainer {
+    n_symbols: usize,
+    symbols_1_byte: [TableEntry; 256],
+    symbols_2_byte: Vec<TableEntry>,
+    phs: LossyPHS,
+    symbols: [Symbol; 2 * TABLE_LENGTH],
+}
+
+impl Trainer {
+    pub(crate) fn new() -> Self {
+        let mut symbols = [Symbol::new(); 2 * TABLE_LENGTH];
+
+        for (code, item) in symbols.iter_mut().enumerate().take(TABLE_LENGTH) {
+            item.add_char(code as u8);
+        }
+
+        Self {
+            n_symbols: 0,
+            symbols,
+            phs: LossyPHS::new(),
+            symbols_1_byte: [TableEntry {
+                val: 0,
+                used: false,
+            }; 256],
+            symbols_2_byte: vec![
+                TableEntry {
+                    val: 0,
+                    used: false
+                };
+                65_536
+            ],
+        }
+    }
+
+    fn insert(&mut self, s: Symbol) -> bool {
+        let idx;
+
+        if s.len == 16 {
+            idx = s.first2byte() as usize;
+            self.symbols_2_byte[idx].val = TABLE_LENGTH + self.n_symbols;
+            self.symbols_2_byte[idx].used = true;
+        } else if s.len == 8 {
+            idx = s.first1byte() as usize;
+            self.symbols_1_byte[idx].val = TABLE_LENGTH + self.n_symbols;
+            self.symbols_1_byte[idx].used = true;
+        } else if !self.phs.add(s.first3byte(), TABLE_LENGTH + self.n_symbols) {
+            return false;
+        }
+
+        self.symbols[TABLE_LENGTH + self.n_symbols] = s;
+        self.n_symbols += 1;
+
+        true
+    }
+
+    pub(crate) fn compress_count(&self, text: &[u8], counters: &mut Counters) {
+        let mut p_start = text.as_ptr();
+        let mut p_end;
+        let mut prev;
+        let mut code = 0;
+        let mut next_char;
+        let mut symbol;
+
+        if text.len() >= 8 {
+            unsafe {
+                p_end = text.as_ptr().add(text.len() - 8);
+            }
+
+            while p_start <= p_end {
+                prev = code;
+
+                unsafe {
+                    symbol = Symbol::with((p_start as *const u64).read_unaligned(), 64);
+                }
+
+                code = self.find_longest_symbol(&symbol);
+
+                counters.incr_c1(code);
+                counters.incr_c2(prev, code);
+
+                if code >= TABLE_LENGTH {
+                    next_char = symbol.first1byte() as usize;
+                    counters.incr_c1(next_char);
+                    counters.incr_c2(prev, next_char);
+                }
+
+                unsafe {
+                    p_start = p_start.add(self.symbols[code].len / 8);
+                }
+            }
+        }
+
+        unsafe {
+            p_end = text.as_ptr().add(text.len());
+        }
+
+        // Remaining bytes (less than 8)
+
+        while p_start < p_end {
+            prev = code;
+
+            unsafe {
+                symbol = load_tail(p_start, p_end.offset_from(p_start) as usize);
+            }
+
+            code = self.find_longest_symbol(&symbol);
+
+            counters.incr_c1(code);
+            counters.incr_c2(prev, code);
+
+            if code >= TABLE_LENGTH {
+                next_char = symbol.first1byte() as usize;
+                counters.incr_c1(next_char);
+                counters.incr_c2(prev, next_char);
+            }
+
+            unsafe {
+                p_start = p_start.add(self.symbols[code].len / 8);
+            }
+        }
+    }
+
+    pub(crate) fn make_table(
+        &mut self,
+        counters: &Counters,
+        sample_frac: usize,
+        max_symbol_length: usize,
+        max_symbols: usize,
+    ) {
+        let mut cands = BinaryHeap::with_capacity(65_536);
+        let mut gain;
+        let mut s1;
+        let mut s2;
+        let mut count;
+        let mut length1;
+
+        for code1 in 0..(TABLE_LENGTH + self.n_symbols) {
+            count = counters.get_from_c1(code1);
+
+            if count < (5 * sample_frac / 128) {
+                continue;
+            }
+
+            s1 = self.symbols[code1];
+
+            length1 = s1.len / 8;
+
+            gain = length1 * count;
+
+            if code1 < 256 {
+                gain *= 8;
+            }
+
+            cands.push(HeapPair(gain, s1));
+
+            if sample_frac >= 128 || length1 >= max_symbol_length {
+                continue;
+            }
+
+            for code2 in 0..(TABLE_LENGTH + self.n_symbols) {
+                s2 = self.symbols[code2];
+
+                if length1 + s2.len / 8 > max_symbol_length {
+                    continue;
+                }
+
+                let new: Symbol = s1.extend(&s2);
+                gain = new.len / 8 * counters.get_from_c2(code1, code2);
+                cands.push(HeapPair(gain, new));
+            }
+        }
+
+        self.clear();
+
+        while !cands.is_empty() && self.n_symbols < max_symbols {
+            let HeapPair(_, sym) = cands.pop().unwrap();
+            self.insert(sym);
+        }
+    }
+
+    /// Returns the trained table, with the codes renumbered like the reference
+    /// implementation does: 2-byte symbols that are not the prefix of a longer
+    /// symbol come first, then the other 2-byte symbols, the 3- to 8-byte
+    /// symbols and finally the 1-byte symbols. This is the order expected by
+    /// `fsst_import`.
+    ///
+    /// In zero-terminated mode the byte 0 gets code 0 and symbols containing
+    /// it are dropped, so that code 0 is the only way to encode it.
+    pub(crate) fn finish(self, zero_terminated: bool) -> SymbolTable {
+        let symbols = self.symbols[TABLE_LENGTH..TABLE_LENGTH + self.n_symbols]
+            .iter()
+            .copied()
+            .filter(|s| !zero_terminated || !contains_zero(s))
+            .collect::<Vec<_>>();
+        let mut order = Vec::with_capacity(symbols.len() + 1);
+        let mut extended = Vec::new();
+
+        if zero_terminated {
+            order.push(Symbol::with(0, 8));
+        }
+
+        for (code, &s) in symbols.iter().enumerate() {
+            if s.len == 16 {
+                if has_extension(&symbols, code) {
+                    extended.push(s);
+                } else {
+                    order.push(s);
+                }
+            }
+        }
+
+        order.extend(extended.iter().rev());
+
+        for len in (3..=SYMBOL_LENGTH).chain(1..=1) {
+            order.extend(symbols.iter().filter(|s| s.len == 8 * len));
+        }
+
+        SymbolTable::from_symbols(&order, zero_terminated)
+    }
+
+    fn find_longest_symbol(&self, text: &Symbol) -> usize {
+        let mut s = self.phs.get(text.first3byte());
+
+        if s.used && text.starts_with(&self.symbols[s.val]) {
+            return s.val;
+        }
+
+        s = self.symbols_2_byte[text.first2byte() as usize];
+        if s.used && text.len >= 16 {
+            return s.val;
+        }
+
+        s = self.symbols_1_byte[text.first1byte() as usize];
+        if s.used {
+            return s.val;
+        }
+
+        text.first1byte() as usize
+    }
+
+    fn clear(&mut self) {
+        for code in 0..(TABLE_LENGTH + self.n_symbols) {
+            let symbol = self.symbols[code];
+
+            if symbol.len == 8 {
+                self.symbols_1_byte[symbol.first1byte() as usize] = TableEntry {
+                    val: 0,
+                    used: false,
+                };
+            } else if symbol.len == 16 {
+                self.symbols_2_byte[symbol.first2byte() as usize] = TableEntry {
+                    val: 0,
+                    used: false,
+                };
+            } else {
+                self.phs.remove(symbol.first3byte());
+            }
+        }
+
+        self.n_symbols = 0;
+    }
+}
+
+/// Loads the last `len < 8` bytes of a string without reading past its end.
+/// The symbol is zero-padded, but its length only covers the `len` bytes.
+///
+/// # Safety
+///
+/// `p` must be valid for reads of `len` bytes.
+#[inline]
+unsafe fn load_tail(p: *const u8, len: usize) -> Symbol {
+    let mut word = [0u8; 8];
+
+    unsafe {
+        ptr::copy_nonoverlapping(p, word.as_mut_ptr(), len.min(8));
+    }
+
+    Symbol::with(u64::from_le_bytes(word), 8 * len.min(8))
+}
+
+fn contains_zero(s: &Symbol) -> bool {
+    (0..s.len / 8).any(|i| (s.value >> (8 * i)) as u8 == 0)
+}
@@ -0,0 +1,37 @@
+use std::io::{self, Read, Write};
+
+/// Writes `value` as a LEB128 variable-length integer.
+pub fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    let mut buffer = [0; 10];
+    let mut len = 0;
+
+    while value >= 0x80 {
+        buffer[len] = (value as u8) | 0x80;
+        value >>= 7;
+        len += 1;
+    }
+
+    buffer[len] = value as u8;
+
+    writer.write_all(&buffer[..len + 1])
+}
+
+/// Reads a LEB128 variable-length integer written by [`write_varint`].
+pub fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0;
+    let mut byte = [0];
+
+    for shift in (0..64).step_by(7) {
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
@@ -0,0 +1,80 @@
+/*
+ * Exercises every function of include/fsst.h through the static library, so
+ * a prototype that drifts from the Rust side fails to build or to run.
+ * Exits with a non-zero status on the first failed check.
+ */
+#include <stdio.h>
+#include <string.h>
+
+#include "fsst.h"
+
+#define CHECK(cond)                                                   \
+   do {                                                               \
+      if (!(cond)) {                                                  \
+         fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+         return 1;                                                    \
+      }                                                               \
+   } while (0)
+
+static int round_trip(int zeroTerminated) {
+   const char *lines[] = {
+      "http://www.example.com/index.html",
+      "http://www.example.com/about.html",
+      "http://www.example.org/",
+      "",
+   };
+   enum { N = sizeof(lines) / sizeof(lines[0]) };
+   unsigned char *strIn[N];
+   size_t lenIn[N];
+
+   for (size_t i = 0; i < N; i++) {
+      strIn[i] = (unsigned char *) lines[i];
+      lenIn[i] = strlen(lines[i]) + (zeroTerminated ? 1 : 0);
+   }
+
+   fsst_encoder_t *created = fsst_create(N, lenIn, strIn, zeroTerminated);
+   fsst_encoder_t *encoder = fsst_duplicate(created);
+   fsst_destroy(created);
+
+   unsigned char output[1024], *strOut[N];
+   size_t lenOut[N];
+   CHECK(fsst_compress(encoder, N, lenIn, strIn, sizeof(output), output, lenOut, strOut) == N);
+   CHECK(fsst_compress(encoder, N, lenIn, strIn, lenOut[0], output, lenOut, strOut) == 1);
+
+   unsigned char exported[FSST_MAXHEADER];
+   unsigned int len = fsst_export(encoder, exported);
+   CHECK(len > 0 && len <= FSST_MAXHEADER);
+
+   fsst_decoder_t imported;
+   CHECK(fsst_import(&imported, exported) == len);
+   CHECK(imported.zeroTerminated == (zeroTerminated ? 1 : 0));
+
+   fsst_decoder_t decoder = fsst_decoder(encoder);
+   CHECK(memcmp(decoder.len, imported.len, sizeof(decoder.len)) == 0);
+   CHECK(memcmp(decoder.symbol, imported.symbol, sizeof(decoder.symbol)) == 0);
+
+   CHECK(fsst_compress(encoder, N, lenIn, strIn, sizeof(output), output, lenOut, strOut) == N);
+
+   for (size_t i = 0; i < N; i++) {
+      unsigned char buffer[64];
+      size_t size = fsst_decompress(&imported, lenOut[i], strOut[i], sizeof(buffer), buffer);
+      CHECK(size == lenIn[i]);
+      CHECK(memcmp(buffer, strIn[i], size) == 0);
+
+      if (zeroTerminated) {
+         CHECK(strlen((char *) strOut[i]) == lenOut[i] - 1);
+      }
+   }
+
+   /* Empty batches may pass NULL arrays */
+   CHECK(fsst_compress(encoder, 0, NULL, NULL, 0, output, NULL, NULL) == 0);
+   CHECK(fsst_decompress(&decoder, 0, NULL, 0, NULL) == 0);
+   fsst_destroy(fsst_create(0, NULL, NULL, zeroTerminated));
+   fsst_destroy(encoder);
+
+   return 0;
+}
+
+int main(void) {
+   return round_trip(0) || round_trip(1);
+}
@@ -0,0 +1,46 @@
+//! Builds `tests/c_api.c` against `include/fsst.h` and the static library and
+//! runs it. `cargo test` only builds the Rust library, so the static library
+//! is built first.
+
+#![cfg(target_os = "linux")]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+#[test]
+fn c_api() -> Result<(), Box<dyn std::error::Error>> {
+    // The test binary is in `target/<profile>/deps`, next to the library
+    let exe = env::current_exe()?;
+    let profile = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let program = env::temp_dir().join(format!("fsst-{}-c_api", process::id()));
+
+    let mut cargo = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()));
+    cargo.args(["build", "--lib", "--quiet", "--manifest-path"]);
+    cargo.arg(crate_dir.join("Cargo.toml"));
+    if profile.ends_with("release") {
+        cargo.arg("--release");
+    }
+    assert!(
+        cargo.status()?.success(),
+        "the static library failed to build"
+    );
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(compiler)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(crate_dir.join("include"))
+        .arg(crate_dir.join("tests/c_api.c"))
+        .arg(profile.join("libfsst.a"))
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&program)
+        .status()?;
+    assert!(status.success(), "tests/c_api.c failed to build");
+
+    let status = Command::new(&program).status()?;
+    std::fs::remove_file(&program)?;
+    assert!(status.success(), "tests/c_api.c failed");
+
+    Ok(())
+}